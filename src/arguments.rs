@@ -2,13 +2,27 @@ use std::env;
 use std::path::PathBuf;
 
 use crate::bookmark::Bookmark;
-
-const SUPPORTED_MENU_PROGRAMS: [&str; 4] = ["bemenu", "dmenu", "rofi", "fzf"];
+use crate::clipboard::Clipboard;
+use crate::menu::{Menu, IGNORE_CASE_PLACEHOLDER, PROMPT_PLACEHOLDER, ROWS_PLACEHOLDER};
+use crate::parsed_file::SUPPORTED_ON_DUPLICATE;
+use crate::plain_text::{SortOrder, SUPPORTED_SORT_ORDERS};
+use crate::subcommand::*;
+
+const SUPPORTED_MENU_PROGRAMS: [&str; 9] = [
+    "bemenu", "dmenu", "rofi", "fzf", "sk", "wofi", "fuzzel", "tofi", "tui",
+];
 const ENV_VARIABLE: &str = "FMARK_DEFAULT_OPTS";
 const DEFAULT_MENU_PROGRAM: &str = "bemenu";
 const DEFAULT_BROWSER: &str = "firefox";
 const DEFAULT_BOOKMARK_FILE_PATH: &str = ".bookmarks";
 const DEFAULT_MENU_ROWS: &str = "20";
+const DEFAULT_SORT_ORDER: &str = "alphabetic";
+const DEFAULT_BACKUP_COUNT: usize = 5;
+const SUPPORTED_FETCH_TITLE: [&str; 2] = ["yes", "no"];
+const DEFAULT_FETCH_TITLE: &str = "no";
+const DEFAULT_CLIPBOARD: &str = "auto";
+const SUPPORTED_IGNORE_CASE: [&str; 2] = ["yes", "no"];
+const DEFAULT_IGNORE_CASE: &str = "yes";
 
 const MENU_ARG_LONG: &str = "--menu";
 const MENU_ARG_SHORT: &str = "-m";
@@ -18,6 +32,14 @@ const PATH_ARG_LONG: &str = "--path";
 const PATH_ARG_SHORT: &str = "-p";
 const ROWS_ARG_LONG: &str = "--rows";
 const ROWS_ARG_SHORT: &str = "-r";
+// Options without a short flag would collide with the short flags of commands
+const SORT_ARG_LONG: &str = "--sort";
+const BACKUPS_ARG_LONG: &str = "--backups";
+const BACKUP_DIR_ARG_LONG: &str = "--backup-dir";
+const FETCH_TITLE_ARG_LONG: &str = "--fetch-title";
+const CLIPBOARD_ARG_LONG: &str = "--clipboard";
+const IGNORE_CASE_ARG_LONG: &str = "--ignore-case";
+const IGNORE_CASE_ARG_SHORT: &str = "-i";
 const HELP_ARG_LONG: &str = "--help";
 const HELP_ARG_SHORT: &str = "-h";
 
@@ -26,6 +48,13 @@ struct PendingArgs {
     browser: Option<String>,
     bookmark_file_path: Option<String>,
     menu_rows: Option<String>,
+    ignore_case: Option<String>,
+    sort_order: Option<String>,
+    backup_count: Option<String>,
+    backup_directory: Option<String>,
+    fetch_title: Option<String>,
+    clipboard: Option<String>,
+    subcommand_args: Option<Vec<String>>,
     help: bool,
 }
 
@@ -34,6 +63,13 @@ pub struct Arguments {
     pub browser: String,
     pub bookmark_file_path: PathBuf,
     pub menu_rows: String,
+    pub ignore_case: bool,
+    pub sort_order: SortOrder,
+    pub backup_count: usize,
+    pub backup_directory: Option<PathBuf>,
+    pub fetch_title: bool,
+    pub clipboard: Clipboard,
+    pub subcommand: Option<Subcommand>,
 }
 
 impl Arguments {
@@ -43,7 +79,9 @@ impl Arguments {
             _ => None,
         };
         let user_defaults: Option<Vec<String>> = match env::var(ENV_VARIABLE) {
-            Ok(user_defaults) => Some(user_defaults.split(' ').map(|s| s.to_string()).collect()),
+            // Quotes keep menu command templates together
+            Ok(user_defaults) => Some(Menu::split_arguments(&user_defaults)?)
+                .filter(|user_defaults| !user_defaults.is_empty()),
             _ => None,
         };
         let pending_values = Self::get_argument_values(args, user_defaults)?;
@@ -57,11 +95,29 @@ impl Arguments {
         let browser = Self::get_browser(pending_values.browser);
         let bookmark_file_path = Self::get_bookmark_file_path(pending_values.bookmark_file_path)?;
         let menu_rows = Self::get_menu_rows(pending_values.menu_rows);
+        let ignore_case = Self::get_ignore_case(pending_values.ignore_case)?;
+        let sort_order = Self::get_sort_order(pending_values.sort_order)?;
+        let backup_count = Self::get_backup_count(pending_values.backup_count)?;
+        let backup_directory = pending_values.backup_directory.map(PathBuf::from);
+        let fetch_title = Self::get_fetch_title(pending_values.fetch_title)?;
+        let clipboard = Clipboard::new(
+            &pending_values
+                .clipboard
+                .unwrap_or(DEFAULT_CLIPBOARD.to_string()),
+        )?;
+        let subcommand = Self::get_subcommand(pending_values.subcommand_args)?;
         Ok(Self {
             menu_program,
             browser,
             bookmark_file_path,
             menu_rows,
+            ignore_case,
+            sort_order,
+            backup_count,
+            backup_directory,
+            fetch_title,
+            clipboard,
+            subcommand,
         })
     }
 
@@ -74,6 +130,13 @@ impl Arguments {
             browser: None,
             bookmark_file_path: None,
             menu_rows: None,
+            ignore_case: None,
+            sort_order: None,
+            backup_count: None,
+            backup_directory: None,
+            fetch_title: None,
+            clipboard: None,
+            subcommand_args: None,
             help: false,
         };
 
@@ -84,16 +147,25 @@ impl Arguments {
                 p.help = true;
                 return Ok(());
             }
-            for i in (0..args.len() - 1).step_by(2) {
-                let arg = args[i].as_str();
-                let value = Some(args[i + 1].clone());
-                match arg {
-                    MENU_ARG_LONG | MENU_ARG_SHORT => p.menu_program = value,
-                    BROWSER_ARG_LONG | BROWSER_ARG_SHORT => p.browser = value,
-                    PATH_ARG_LONG | PATH_ARG_SHORT => p.bookmark_file_path = value,
-                    ROWS_ARG_LONG | ROWS_ARG_SHORT => p.menu_rows = value,
-                    _ => return Err(Self::unrecognized_arg_message(arg)),
-                }
+            let mut args = args.into_iter();
+            while let Some(arg) = args.next() {
+                let option = match arg.as_str() {
+                    MENU_ARG_LONG | MENU_ARG_SHORT => &mut p.menu_program,
+                    BROWSER_ARG_LONG | BROWSER_ARG_SHORT => &mut p.browser,
+                    PATH_ARG_LONG | PATH_ARG_SHORT => &mut p.bookmark_file_path,
+                    ROWS_ARG_LONG | ROWS_ARG_SHORT => &mut p.menu_rows,
+                    IGNORE_CASE_ARG_LONG | IGNORE_CASE_ARG_SHORT => &mut p.ignore_case,
+                    SORT_ARG_LONG => &mut p.sort_order,
+                    BACKUPS_ARG_LONG => &mut p.backup_count,
+                    BACKUP_DIR_ARG_LONG => &mut p.backup_directory,
+                    FETCH_TITLE_ARG_LONG => &mut p.fetch_title,
+                    CLIPBOARD_ARG_LONG => &mut p.clipboard,
+                    _ => return Err(Self::unrecognized_arg_message(&arg)),
+                };
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("Error: Missing value for '{}'.", arg))?;
+                *option = Some(value);
             }
 
             Ok(())
@@ -104,22 +176,97 @@ impl Arguments {
         }
 
         if let Some(args) = args {
-            process_args(args, &mut p)?;
+            let (args, subcommand_args) = Self::split_subcommand_args(args);
+            if let Some(subcommand_args) = &subcommand_args {
+                if subcommand_args.contains(&HELP_ARG_LONG.to_string())
+                    || subcommand_args.contains(&HELP_ARG_SHORT.to_string())
+                {
+                    p.help = true;
+                }
+            }
+            if !args.is_empty() {
+                process_args(args, &mut p)?;
+            }
+            p.subcommand_args = subcommand_args;
         }
 
         Ok(p)
     }
 
+    fn get_sort_order(sort_order: Option<String>) -> Result<SortOrder, String> {
+        match sort_order {
+            Some(sort_order) => SortOrder::new(&sort_order),
+            None => SortOrder::new(DEFAULT_SORT_ORDER),
+        }
+    }
+
+    fn get_backup_count(backup_count: Option<String>) -> Result<usize, String> {
+        match backup_count {
+            Some(backup_count) => backup_count
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid number of backups: {}", backup_count)),
+            None => Ok(DEFAULT_BACKUP_COUNT),
+        }
+    }
+
+    fn get_fetch_title(fetch_title: Option<String>) -> Result<bool, String> {
+        match fetch_title.as_deref().unwrap_or(DEFAULT_FETCH_TITLE) {
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            fetch_title => Err(format!(
+                "Unsupported fetch title option: {}. Supported are '{}'.",
+                fetch_title,
+                SUPPORTED_FETCH_TITLE.join("', '")
+            )),
+        }
+    }
+
+    // The subcommand is the first argument that is neither an option nor an option's value
+    fn split_subcommand_args(mut args: Vec<String>) -> (Vec<String>, Option<Vec<String>>) {
+        let mut index = 0;
+        while let Some(arg) = args.get(index) {
+            match arg.as_str() {
+                HELP_ARG_LONG | HELP_ARG_SHORT => index += 1,
+                arg if arg.starts_with('-') => index += 2,
+                _ => {
+                    let subcommand_args = args.split_off(index);
+                    return (args, Some(subcommand_args));
+                }
+            }
+        }
+        (args, None)
+    }
+
+    fn get_subcommand(subcommand_args: Option<Vec<String>>) -> Result<Option<Subcommand>, String> {
+        match subcommand_args {
+            Some(subcommand_args) => Subcommand::new(subcommand_args).map(Some),
+            None => Ok(None),
+        }
+    }
+
     fn get_menu_program(menu_program: Option<String>) -> Result<String, String> {
         let menu_program = match menu_program {
             Some(menu_program) => menu_program,
             None => DEFAULT_MENU_PROGRAM.to_string(),
         };
 
-        if SUPPORTED_MENU_PROGRAMS.contains(&menu_program.as_str()) {
-            Ok(menu_program)
+        // Programs that are not built in are run as a command template
+        if menu_program.trim().is_empty() {
+            Err("Missing menu program.".to_string())
         } else {
-            Err(format!("Unsupported menu program: {}", menu_program))
+            Ok(menu_program)
+        }
+    }
+
+    fn get_ignore_case(ignore_case: Option<String>) -> Result<bool, String> {
+        match ignore_case.as_deref().unwrap_or(DEFAULT_IGNORE_CASE) {
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            ignore_case => Err(format!(
+                "Unsupported ignore case option: {}. Supported are '{}'.",
+                ignore_case,
+                SUPPORTED_IGNORE_CASE.join("', '")
+            )),
         }
     }
 
@@ -175,15 +322,25 @@ impl Arguments {
 
     #[rustfmt::skip]
     pub fn print_help_message() {
-        println!("Usage: fmark [OPTIONS]\n");
+        println!("Usage: fmark [OPTIONS] [COMMAND]\n");
         println!(
             "This program can search and modify a formatted plain text list of websites.\n"
         );
         println!("format:");
-        println!("  {}\n", Bookmark::default().to_line(0, 0));
+        print!("  {}", Bookmark::default().to_line(0, 0));
+        println!("  Optional segments: {{G}}{{comma,separated,tags}} {{D}}{{description}}");
+        println!("{:21}{{A}}{{added}} {{V}}{{last visited}} {{N}}{{visit count}}", "");
+        println!("{:21}(times are seconds since the Unix epoch)", "");
+        println!("  Braces and backslashes inside a field are escaped with a backslash (e.g. '\\{{').\n");
         println!("Options:");
         println!("  {}, {:19}Menu program to use.", MENU_ARG_SHORT, MENU_ARG_LONG);
-        println!("{:25}Supported programs are '{}'.", "", SUPPORTED_MENU_PROGRAMS.join("', '"));
+        println!("{:25}Supported programs are '{}',", "", SUPPORTED_MENU_PROGRAMS[..4].join("', '"));
+        println!("{:25}'{}'.", "", SUPPORTED_MENU_PROGRAMS[4..].join("', '"));
+        println!("{:25}'tui' is built in: Enter chooses, Tab edits the highlighted", "");
+        println!("{:25}item, Alt+Enter keeps the typed text and Esc cancels.", "");
+        println!("{:25}Any other value is run as a command template, where", "");
+        println!("{:25}'{}', '{}' and '{}<flag>}}' are replaced", "", PROMPT_PLACEHOLDER, ROWS_PLACEHOLDER, IGNORE_CASE_PLACEHOLDER);
+        println!("{:25}(e.g. 'my-menu -p {} -l {} {}-i}}').", "", PROMPT_PLACEHOLDER, ROWS_PLACEHOLDER, IGNORE_CASE_PLACEHOLDER);
         println!("{:25}Default: ({})", "", DEFAULT_MENU_PROGRAM);
         println!("  {}, {:19}Browser command URLs will be passed to.", BROWSER_ARG_SHORT, BROWSER_ARG_LONG);
         println!("{:25}Default: ({})", "",DEFAULT_BROWSER);
@@ -191,7 +348,69 @@ impl Arguments {
         println!("{:25}Default: ($HOME/{})", "", DEFAULT_BOOKMARK_FILE_PATH);
         println!("  {}, {:19}Number of rows to show in the menu.", ROWS_ARG_SHORT, ROWS_ARG_LONG);
         println!("{:25}Default: ({})", "",DEFAULT_MENU_ROWS);
+        println!("  {}, {:19}Ignore case when filtering the menu.", IGNORE_CASE_ARG_SHORT, IGNORE_CASE_ARG_LONG);
+        println!("{:25}Supported are '{}'.", "", SUPPORTED_IGNORE_CASE.join("', '"));
+        println!("{:25}Default: ({})", "", DEFAULT_IGNORE_CASE);
+        println!("{:6}{:19}Order of the bookmarks in the menu and list.", "", SORT_ARG_LONG);
+        println!("{:25}Supported orders are '{}'.", "", SUPPORTED_SORT_ORDERS.join("', '"));
+        println!("{:25}Default: ({})", "", DEFAULT_SORT_ORDER);
+        println!("{:6}{:19}Number of backups to keep, 0 disables them.", "", BACKUPS_ARG_LONG);
+        println!("{:25}Default: ({})", "", DEFAULT_BACKUP_COUNT);
+        println!("{:6}{:19}Directory to keep backups in.", "", BACKUP_DIR_ARG_LONG);
+        println!("{:25}Default: (the directory of the bookmark file)", "");
+        println!("{:6}{:19}Prefill the title of new bookmarks with the page title.", "", FETCH_TITLE_ARG_LONG);
+        println!("{:25}Supported are '{}'.", "", SUPPORTED_FETCH_TITLE.join("', '"));
+        println!("{:25}Default: ({})", "", DEFAULT_FETCH_TITLE);
+        println!("{:6}{:19}Command that prints the clipboard, a URL in it prefills", "", CLIPBOARD_ARG_LONG);
+        println!("{:25}the URL of new bookmarks. 'auto' tries wl-paste, xclip", "");
+        println!("{:25}and xsel, 'none' disables it.", "");
+        println!("{:25}Default: ({})", "", DEFAULT_CLIPBOARD);
         println!("  {}, {:19}Show this help message and exit.\n", HELP_ARG_SHORT, HELP_ARG_LONG);
+        println!("Commands (the menu is opened when no command is given):");
+        println!("  {:23}Print bookmarks without opening the menu.", LIST_SUBCOMMAND);
+        println!("{:25}{}, {:15}Only show bookmarks in this category.", "", CATEGORY_ARG_SHORT, CATEGORY_ARG_LONG);
+        println!("{:25}{}, {:15}Only show titles containing this text.", "", TITLE_ARG_SHORT, TITLE_ARG_LONG);
+        println!("{:25}{}, {:15}Only show URLs containing this text.", "", URL_ARG_SHORT, URL_ARG_LONG);
+        println!("{:25}{}, {:15}Only show bookmarks with this tag.", "", TAG_ARG_SHORT, TAG_ARG_LONG);
+        println!("  {:23}Add a bookmark without opening the menu.", ADD_SUBCOMMAND);
+        println!("{:25}{}, {:15}Title of the bookmark.", "", TITLE_ARG_SHORT, TITLE_ARG_LONG);
+        println!("{:25}{:19}Fetched from the page when missing.", "", "");
+        println!("{:25}{}, {:15}Category of the bookmark.", "", CATEGORY_ARG_SHORT, CATEGORY_ARG_LONG);
+        println!("{:25}{}, {:15}URL of the bookmark.", "", URL_ARG_SHORT, URL_ARG_LONG);
+        println!("{:25}{}, {:15}Comma separated tags of the bookmark.", "", TAGS_ARG_SHORT, TAGS_ARG_LONG);
+        println!("{:25}{}, {:15}Description of the bookmark.", "", DESCRIPTION_ARG_SHORT, DESCRIPTION_ARG_LONG);
+        println!("{:25}{}, {:15}What to do when the URL is already bookmarked.", "", ON_DUPLICATE_ARG_SHORT, ON_DUPLICATE_ARG_LONG);
+        println!("{:25}{:19}Supported are '{}'.", "", "", SUPPORTED_ON_DUPLICATE.join("', '"));
+        println!("  {:23}Remove the bookmark with this URL.", format!("{} <url>", REMOVE_SUBCOMMAND));
+        println!("{:25}{}, {:15}Which copy to remove when the URL is bookmarked more than once.", "", COPY_ARG_SHORT, COPY_ARG_LONG);
+        println!("  {:23}Modify the bookmark with this URL.", format!("{} <url>", MODIFY_SUBCOMMAND));
+        println!("{:25}{}, {:15}New title of the bookmark.", "", TITLE_ARG_SHORT, TITLE_ARG_LONG);
+        println!("{:25}{}, {:15}New category of the bookmark.", "", CATEGORY_ARG_SHORT, CATEGORY_ARG_LONG);
+        println!("{:25}{}, {:15}New URL of the bookmark.", "", URL_ARG_SHORT, URL_ARG_LONG);
+        println!("{:25}{}, {:15}New comma separated tags of the bookmark.", "", TAGS_ARG_SHORT, TAGS_ARG_LONG);
+        println!("{:25}{}, {:15}New description of the bookmark.", "", DESCRIPTION_ARG_SHORT, DESCRIPTION_ARG_LONG);
+        println!("{:25}{}, {:15}Which copy to modify when the URL is bookmarked more than once.", "", COPY_ARG_SHORT, COPY_ARG_LONG);
+        println!("  {:23}Merge bookmarks from a file into the bookmark file.", format!("{} <file>", IMPORT_SUBCOMMAND));
+        println!("{:25}{}, {:15}Format of the file.", "", FORMAT_ARG_SHORT, FORMAT_ARG_LONG);
+        println!("{:25}{:19}Supported formats are '{}'.", "", "", SUPPORTED_FORMATS.join("', '"));
+        println!("{:25}{}, {:15}What to do with URLs that are already bookmarked.", "", ON_DUPLICATE_ARG_SHORT, ON_DUPLICATE_ARG_LONG);
+        println!("{:25}{:19}Supported are '{}'. Default: ({})", "", "", SUPPORTED_ON_DUPLICATE.join("', '"), DEFAULT_IMPORT_ON_DUPLICATE);
+        println!("  {:23}Print the bookmark file in another format.", EXPORT_SUBCOMMAND);
+        println!("{:25}{}, {:15}Format to print.", "", FORMAT_ARG_SHORT, FORMAT_ARG_LONG);
+        println!("{:25}{:19}Supported formats are '{}'.", "", "", SUPPORTED_FORMATS.join("', '"));
+        println!("  {:23}List backups, or restore the backup with this number.", format!("{} [number]", RESTORE_SUBCOMMAND));
+        println!("{:25}Restoring clears the undo and redo history.", "");
+        println!("  {:23}Undo the last change to the bookmark file.", UNDO_SUBCOMMAND);
+        println!("  {:23}Redo the last undone change.", REDO_SUBCOMMAND);
+        println!("  {:23}List bookmarks whose URLs only differ in case, trailing slash,", DEDUPE_SUBCOMMAND);
+        println!("{:25}default port, fragment or utm_* parameters.", "");
+        println!("  {:23}Check every URL and report broken links and permanent redirects.", CHECK_SUBCOMMAND);
+        println!("{:25}{}, {:15}Number of URLs to check at the same time.", "", JOBS_ARG_SHORT, JOBS_ARG_LONG);
+        println!("{:25}{:19}Default: ({})", "", "", DEFAULT_CHECK_JOBS);
+        println!("{:25}{}, {:15}Seconds to wait for each URL.", "", TIMEOUT_ARG_SHORT, TIMEOUT_ARG_LONG);
+        println!("{:25}{:19}Default: ({})", "", "", DEFAULT_CHECK_TIMEOUT);
+        println!("{:25}{}, {:15}What to do with permanent redirects.", "", REDIRECTS_ARG_SHORT, REDIRECTS_ARG_LONG);
+        println!("{:25}{:19}Supported are '{}'. Default: ({})\n", "", "", SUPPORTED_REDIRECTS.join("', '"), SUPPORTED_REDIRECTS[0]);
         println!("Environment Variables:");
         println!("{:25}Default options", ENV_VARIABLE);
         println!("{:25}(e.g. '--menu {} --rows {}')", "", DEFAULT_MENU_PROGRAM, DEFAULT_MENU_ROWS);
@@ -213,8 +432,12 @@ mod tests {
         let menu_program = Arguments::get_menu_program(Some("bemenu".to_string()));
         assert_eq!(menu_program.unwrap(), "bemenu");
 
-        // Test with an unsupported menu program
-        let menu_program = Arguments::get_menu_program(Some("unsupported".to_string()));
+        // Test with a command template
+        let menu_program = Arguments::get_menu_program(Some("wofi --dmenu".to_string()));
+        assert_eq!(menu_program.unwrap(), "wofi --dmenu");
+
+        // Test with an empty menu program
+        let menu_program = Arguments::get_menu_program(Some(" ".to_string()));
         assert!(menu_program.is_err());
 
         // Test with None, should return the default menu program
@@ -265,4 +488,113 @@ mod tests {
         let rows = Arguments::get_menu_rows(None);
         assert_eq!(rows, DEFAULT_MENU_ROWS);
     }
+
+    #[test]
+    fn test_arguments_get_sort_order() {
+        // Test with a supported sort order
+        let sort_order = Arguments::get_sort_order(Some("frecency".to_string()));
+        assert!(matches!(sort_order, Ok(SortOrder::Frecency)));
+
+        // Test with an unsupported sort order
+        let sort_order = Arguments::get_sort_order(Some("unsupported".to_string()));
+        assert!(sort_order.is_err());
+
+        // Test with None, should return the default sort order
+        let sort_order = Arguments::get_sort_order(None);
+        assert!(matches!(sort_order, Ok(SortOrder::Alphabetic)));
+    }
+
+    #[test]
+    fn test_arguments_get_backup_count() {
+        // Test with a valid number of backups
+        let backup_count = Arguments::get_backup_count(Some("0".to_string()));
+        assert_eq!(backup_count.unwrap(), 0);
+
+        // Test with an invalid number of backups
+        let backup_count = Arguments::get_backup_count(Some("-1".to_string()));
+        assert!(backup_count.is_err());
+
+        // Test with None, should return the default number of backups
+        let backup_count = Arguments::get_backup_count(None);
+        assert_eq!(backup_count.unwrap(), DEFAULT_BACKUP_COUNT);
+    }
+
+    #[test]
+    fn test_arguments_get_ignore_case() {
+        assert!(!Arguments::get_ignore_case(Some("no".to_string())).unwrap());
+        assert!(Arguments::get_ignore_case(Some("false".to_string())).is_err());
+
+        // Test with None, should return the default
+        assert!(Arguments::get_ignore_case(None).unwrap());
+    }
+
+    #[test]
+    fn test_arguments_get_fetch_title() {
+        assert!(Arguments::get_fetch_title(Some("yes".to_string())).unwrap());
+        assert!(Arguments::get_fetch_title(Some("true".to_string())).is_err());
+
+        // Test with None, should return the default
+        assert!(!Arguments::get_fetch_title(None).unwrap());
+    }
+
+    #[test]
+    fn test_arguments_split_subcommand_args() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        // Test with options only
+        let (options, subcommand_args) = Arguments::split_subcommand_args(args(&["-m", "fzf"]));
+        assert_eq!(options, args(&["-m", "fzf"]));
+        assert!(subcommand_args.is_none());
+
+        // Test with options followed by a subcommand
+        let (options, subcommand_args) =
+            Arguments::split_subcommand_args(args(&["-m", "fzf", "list", "-c", "News"]));
+        assert_eq!(options, args(&["-m", "fzf"]));
+        assert_eq!(subcommand_args.unwrap(), args(&["list", "-c", "News"]));
+
+        // Test with a subcommand only
+        let (options, subcommand_args) = Arguments::split_subcommand_args(args(&["list"]));
+        assert!(options.is_empty());
+        assert_eq!(subcommand_args.unwrap(), args(&["list"]));
+
+        // Test with a flag that takes no value before and after the subcommand
+        let (options, subcommand_args) =
+            Arguments::split_subcommand_args(args(&["-h", "-m", "fzf", "list"]));
+        assert_eq!(options, args(&["-h", "-m", "fzf"]));
+        assert_eq!(subcommand_args.unwrap(), args(&["list"]));
+        let (options, subcommand_args) =
+            Arguments::split_subcommand_args(args(&["-m", "fzf", "list", "-h"]));
+        assert_eq!(options, args(&["-m", "fzf"]));
+        assert_eq!(subcommand_args.unwrap(), args(&["list", "-h"]));
+    }
+
+    #[test]
+    fn test_arguments_get_argument_values() {
+        let args = |args: &[&str]| Some(args.iter().map(|arg| arg.to_string()).collect());
+
+        let values = Arguments::get_argument_values(args(&["-h", "list"]), None).unwrap();
+        assert!(values.help);
+        assert_eq!(values.subcommand_args.unwrap(), ["list"]);
+        let values = Arguments::get_argument_values(args(&["list", "--help"]), None).unwrap();
+        assert!(values.help);
+
+        let values =
+            Arguments::get_argument_values(args(&["-m", "fzf", "-r", "5"]), args(&["-m", "tui"]))
+                .unwrap();
+        assert_eq!(values.menu_program.as_deref(), Some("fzf"));
+        assert_eq!(values.menu_rows.as_deref(), Some("5"));
+
+        // Test that command flags are not taken for global options
+        let values =
+            Arguments::get_argument_values(args(&["--clipboard", "x", "add", "-c", "y"]), None)
+                .unwrap();
+        assert_eq!(values.clipboard.as_deref(), Some("x"));
+        assert_eq!(values.subcommand_args.unwrap(), ["add", "-c", "y"]);
+        assert!(
+            Arguments::get_argument_values(args(&["-c", "x", "add", "-c", "y"]), None).is_err()
+        );
+
+        assert!(Arguments::get_argument_values(args(&["-m"]), None).is_err());
+        assert!(Arguments::get_argument_values(args(&["-x", "fzf"]), None).is_err());
+    }
 }
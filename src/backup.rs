@@ -0,0 +1,180 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const BACKUP_FILE_EXTENSION: &str = "backup";
+
+pub struct Backup {
+    path: PathBuf,
+    timestamp: u64,
+}
+
+impl Backup {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn read(&self) -> Result<String, String> {
+        fs::read_to_string(&self.path)
+            .map_err(|error| format!("Failed to read backup {}: {}", self.path.display(), error))
+    }
+
+    pub fn date(&self) -> String {
+        let seconds = self.timestamp / 1000;
+        let (days, seconds) = (seconds / 86400, seconds % 86400);
+
+        // Civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+        let z = days + 719468;
+        let era = z / 146097;
+        let day_of_era = z % 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+            year,
+            month,
+            day,
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
+    }
+}
+
+pub struct Backups {
+    directory: PathBuf,
+    file_name: String,
+    count: usize,
+}
+
+impl Backups {
+    pub fn new(file_path: &Path, directory: Option<PathBuf>, count: usize) -> Self {
+        let file_path = fs::canonicalize(file_path).unwrap_or_else(|_| file_path.to_path_buf());
+        let directory = match directory {
+            Some(directory) => directory,
+            None => match file_path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            },
+        };
+        let file_name = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Self {
+            directory,
+            file_name,
+            count,
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn create(&self, contents: &str) -> io::Result<()> {
+        if self.count == 0 {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.directory)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        fs::write(self.backup_path(timestamp), contents)?;
+
+        for backup in self.list()?.iter().skip(self.count) {
+            fs::remove_file(backup.path())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn list(&self) -> io::Result<Vec<Backup>> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+
+        let prefix = format!(".{}.", self.file_name);
+        let suffix = format!(".{}", BACKUP_FILE_EXTENSION);
+        let mut backups = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let timestamp = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|name| name.strip_suffix(&suffix))
+                .and_then(|timestamp| timestamp.parse().ok());
+            if let Some(timestamp) = timestamp {
+                backups.push(Backup { path, timestamp });
+            }
+        }
+        backups.sort_by_key(|backup| Reverse(backup.timestamp));
+
+        Ok(backups)
+    }
+
+    fn backup_path(&self, timestamp: u64) -> PathBuf {
+        self.directory.join(format!(
+            ".{}.{}.{}",
+            self.file_name, timestamp, BACKUP_FILE_EXTENSION
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backups_create() {
+        let directory = std::env::temp_dir().join(format!("fmark_backups_{}", std::process::id()));
+        let backups = Backups::new(Path::new("bookmarks"), Some(directory.clone()), 2);
+        assert!(backups.list().unwrap().is_empty());
+
+        for contents in ["first", "second", "third"] {
+            backups.create(contents).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        // Test that only the newest backups are kept, newest first
+        let list = backups.list().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].read().unwrap(), "third");
+        assert_eq!(list[1].read().unwrap(), "second");
+
+        // Test that backups of other files are ignored
+        let other = Backups::new(Path::new("other"), Some(directory.clone()), 2);
+        assert!(other.list().unwrap().is_empty());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_backup_date() {
+        let backup = |timestamp| Backup {
+            path: PathBuf::new(),
+            timestamp,
+        };
+        assert_eq!(backup(0).date(), "1970-01-01 00:00:00 UTC");
+        assert_eq!(backup(951_782_400_000).date(), "2000-02-29 00:00:00 UTC");
+        assert_eq!(backup(1_760_615_999_000).date(), "2025-10-16 11:59:59 UTC");
+    }
+}
@@ -1,4 +1,7 @@
 use std::borrow::Cow;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::{CATEGORY_MAX_LENGTH, TITLE_MAX_LENGTH};
 
@@ -9,23 +12,98 @@ const CATEGORY_MARKER: &str = "C";
 const URL_MARKER: &str = "U";
 const URL_MAX_LENGTH: usize = 2048;
 
+const TAGS_MARKER: &str = "G";
+const TAGS_SEPARATOR: char = ',';
+
+const DESCRIPTION_MARKER: &str = "D";
+
+const ADDED_MARKER: &str = "A";
+const LAST_VISITED_MARKER: &str = "V";
+const VISIT_COUNT_MARKER: &str = "N";
+
+const DAY_IN_SECONDS: u64 = 24 * 60 * 60;
+const FRECENCY_WEIGHTS: [(u64, u64); 4] = [(4, 100), (14, 70), (31, 50), (90, 30)];
+const FRECENCY_MIN_WEIGHT: u64 = 10;
+
 const SEGMENT_START: char = '{';
 const SEGMENT_END: char = '}';
+const ESCAPE: char = '\\';
 
 #[derive(Clone, PartialEq)]
 pub struct Bookmark {
     title: String,
     category: String,
     url: String,
+    tags: Vec<String>,
+    description: String,
+    added: Option<u64>,
+    last_visited: Option<u64>,
+    visit_count: u64,
 }
 
 impl Bookmark {
     pub fn new(title: String, category: String, url: String) -> Self {
         Self {
-            title,
-            category,
-            url,
+            title: Self::single_line(title),
+            category: Self::single_line(category),
+            url: Self::single_line(url),
+            tags: Vec::new(),
+            description: String::new(),
+            added: None,
+            last_visited: None,
+            visit_count: 0,
+        }
+    }
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = Vec::new();
+        for tag in tags {
+            let tag = Self::single_line(tag);
+            let tag = tag.trim();
+            if !tag.is_empty() && !self.tags.iter().any(|t| t == tag) {
+                self.tags.push(tag.to_string());
+            }
         }
+        self
+    }
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = description
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        self
+    }
+    pub fn with_added(mut self, added: Option<u64>) -> Self {
+        self.added = added;
+        self
+    }
+    pub fn with_visits(mut self, last_visited: Option<u64>, visit_count: u64) -> Self {
+        self.last_visited = last_visited;
+        self.visit_count = visit_count;
+        self
+    }
+    pub fn with_history_of(self, bookmark: &Bookmark) -> Self {
+        self.with_added(bookmark.added)
+            .with_visits(bookmark.last_visited, bookmark.visit_count)
+    }
+    pub fn merged_with(&self, other: &Bookmark) -> Self {
+        let mut tags = self.tags.clone();
+        tags.extend(other.tags.iter().cloned());
+        let description = match self.description.is_empty() {
+            true => &other.description,
+            false => &self.description,
+        };
+        let added = match (self.added, other.added) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        Self::new(self.title.clone(), self.category.clone(), self.url.clone())
+            .with_tags(tags)
+            .with_description(description)
+            .with_added(added)
+            .with_visits(
+                self.last_visited.max(other.last_visited),
+                self.visit_count + other.visit_count,
+            )
     }
     pub fn default() -> Self {
         let title = "Project's Github".to_string();
@@ -35,6 +113,11 @@ impl Bookmark {
             title,
             category,
             url,
+            tags: Vec::new(),
+            description: String::new(),
+            added: None,
+            last_visited: None,
+            visit_count: 0,
         }
     }
     pub fn title(&self) -> &str {
@@ -46,97 +129,292 @@ impl Bookmark {
     pub fn url(&self) -> &str {
         &self.url
     }
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+    pub fn added(&self) -> Option<u64> {
+        self.added
+    }
+    pub fn last_visited(&self) -> Option<u64> {
+        self.last_visited
+    }
+    pub fn visit_count(&self) -> u64 {
+        self.visit_count
+    }
+    pub fn frecency(&self, now: u64) -> u64 {
+        let last_visited = match self.last_visited {
+            Some(last_visited) => last_visited,
+            None => return 0,
+        };
+        let age_in_days = now.saturating_sub(last_visited) / DAY_IN_SECONDS;
+        let weight = FRECENCY_WEIGHTS
+            .iter()
+            .find(|(days, _)| age_in_days < *days)
+            .map_or(FRECENCY_MIN_WEIGHT, |(_, weight)| *weight);
+        self.visit_count * weight
+    }
+    pub fn visit(&mut self, timestamp: u64) {
+        self.last_visited = Some(timestamp);
+        self.visit_count += 1;
+    }
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
 
-    pub fn to_line(&self, mut title_padding: usize, mut category_padding: usize) -> String {
-        let title_char_count = self.title().chars().count();
-        if title_padding >= TITLE_MAX_LENGTH - 1 {
-            title_padding = TITLE_MAX_LENGTH.saturating_sub(title_char_count) + 1
-        } else {
-            title_padding = title_padding.saturating_sub(title_char_count) + 1
-        }
+    pub fn parse_tags(tags: &str) -> Vec<String> {
+        tags.split(TAGS_SEPARATOR)
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    }
 
-        let category_char_count = self.category().chars().count();
-        if category_padding >= CATEGORY_MAX_LENGTH - 1 {
-            category_padding = CATEGORY_MAX_LENGTH.saturating_sub(category_char_count) + 1
-        } else {
-            category_padding = category_padding.saturating_sub(category_char_count) + 1
+    pub fn details(&self) -> String {
+        let mut details = format!(
+            "Title:       {}\nCategory:    {}\nURL:         {}\n",
+            self.title, self.category, self.url
+        );
+        if !self.tags.is_empty() {
+            details.push_str(&format!("Tags:        {}\n", self.tags.join(", ")));
+        }
+        if !self.description.is_empty() {
+            details.push_str(&format!("Description: {}\n", self.description));
         }
+        details
+    }
+
+    pub fn joined_tags(&self) -> String {
+        self.tags.join(&TAGS_SEPARATOR.to_string())
+    }
+
+    pub fn timestamp_now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0)
+    }
+
+    pub fn to_line(&self, title_padding: usize, category_padding: usize) -> String {
+        self.format_line(title_padding, category_padding, false)
+    }
 
-        let title: Cow<str> = if title_char_count > TITLE_MAX_LENGTH {
+    // Menu lines leave out the history, so filtering does not match timestamps and counts
+    pub fn to_menu_line(&self, title_padding: usize, category_padding: usize) -> String {
+        self.format_line(title_padding, category_padding, true)
+    }
+
+    fn format_line(
+        &self,
+        mut title_padding: usize,
+        mut category_padding: usize,
+        for_menu: bool,
+    ) -> String {
+        // Only menu lines are truncated, the file and previews keep the full fields
+        let title_char_count = self.title().chars().count();
+        let title: Cow<str> = if for_menu && title_char_count > TITLE_MAX_LENGTH {
             format!("{:.TITLE_MAX_LENGTH$}", self.title).into()
         } else {
             Cow::Borrowed(&self.title)
         };
+        let title = Self::escape(title);
 
-        let category: Cow<str> = if category_char_count > CATEGORY_MAX_LENGTH {
+        let category_char_count = self.category().chars().count();
+        let category: Cow<str> = if for_menu && category_char_count > CATEGORY_MAX_LENGTH {
             format!("{:.CATEGORY_MAX_LENGTH$}", self.category).into()
         } else {
             Cow::Borrowed(&self.category)
         };
+        let category = Self::escape(category);
 
         let url: Cow<str> = if self.url.len() > URL_MAX_LENGTH {
             format!("{:.URL_MAX_LENGTH$}", self.url).into()
         } else {
             Cow::Borrowed(&self.url)
         };
+        let url = Self::escape(url);
+
+        let title_char_count = title.chars().count();
+        if title_padding >= TITLE_MAX_LENGTH - 1 {
+            title_padding = TITLE_MAX_LENGTH.saturating_sub(title_char_count) + 1
+        } else {
+            title_padding = title_padding.saturating_sub(title_char_count) + 1
+        }
+
+        let category_char_count = category.chars().count();
+        if category_padding >= CATEGORY_MAX_LENGTH - 1 {
+            category_padding = CATEGORY_MAX_LENGTH.saturating_sub(category_char_count) + 1
+        } else {
+            category_padding = category_padding.saturating_sub(category_char_count) + 1
+        }
+
+        let mut optional_segments = String::new();
+        if !self.tags.is_empty() {
+            Self::push_segment(&mut optional_segments, TAGS_MARKER, &self.joined_tags());
+        }
+        if !self.description.is_empty() {
+            Self::push_segment(
+                &mut optional_segments,
+                DESCRIPTION_MARKER,
+                &self.description,
+            );
+        }
+        if let Some(added) = self.added.filter(|_| !for_menu) {
+            Self::push_segment(&mut optional_segments, ADDED_MARKER, &added.to_string());
+        }
+        if let Some(last_visited) = self.last_visited.filter(|_| !for_menu) {
+            Self::push_segment(
+                &mut optional_segments,
+                LAST_VISITED_MARKER,
+                &last_visited.to_string(),
+            );
+        }
+        if self.visit_count > 0 && !for_menu {
+            Self::push_segment(
+                &mut optional_segments,
+                VISIT_COUNT_MARKER,
+                &self.visit_count.to_string(),
+            );
+        }
 
         format!(
-            "{{{}}}{{{}}}{:title_padding$}{{{}}}{{{}}}{:category_padding$}{{{}}}{{{}}}\n",
-            TITLE_MARKER, title, "", CATEGORY_MARKER, category, "", URL_MARKER, url
+            "{{{}}}{{{}}}{:title_padding$}{{{}}}{{{}}}{:category_padding$}{{{}}}{{{}}}{}\n",
+            TITLE_MARKER,
+            title,
+            "",
+            CATEGORY_MARKER,
+            category,
+            "",
+            URL_MARKER,
+            url,
+            optional_segments
         )
     }
 
+    fn push_segment(segments: &mut String, marker: &str, field: &str) {
+        segments.push_str(&format!(
+            " {{{}}}{{{}}}",
+            marker,
+            Self::escape(Cow::Borrowed(field))
+        ));
+    }
+
     pub fn from_line(line: &str) -> Option<Bookmark> {
         let mut segments: Vec<String> = Vec::new();
         let mut segment = String::new();
         let mut capture = false;
-        for c in line.chars() {
-            if c == SEGMENT_START && !capture {
-                capture = true;
-                segment.clear();
-            } else if c == '\n' && capture {
-                capture = false;
-                segment.clear();
-            }
-            if capture {
-                segment.push(c);
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if !capture {
+                if c == SEGMENT_START {
+                    capture = true;
+                    segment.clear();
+                }
+                continue;
             }
-            if c == SEGMENT_END && capture {
-                capture = false;
-                if !segment.is_empty() {
+            match c {
+                ESCAPE => segment.push(Self::unescape(&mut chars)),
+                SEGMENT_END => {
+                    capture = false;
                     segments.push(segment.clone());
                 }
+                '\n' => {
+                    capture = false;
+                    segment.clear();
+                }
+                _ => segment.push(c),
             }
         }
-        if segments.len() == 6 {
+        if segments.len() >= 6 && segments.len().is_multiple_of(2) {
             let mut title = None;
             let mut category = None;
             let mut url = None;
+            let mut tags = None;
+            let mut description = None;
+            let mut added = None;
+            let mut last_visited = None;
+            let mut visit_count = None;
             for i in (0..segments.len()).step_by(2) {
-                let marker = segments[i]
-                    .trim_matches(&[SEGMENT_START, SEGMENT_END] as &[char])
-                    .trim();
-
-                let field = segments[i + 1]
-                    .trim_matches(&[SEGMENT_START, SEGMENT_END] as &[char])
-                    .trim();
+                let marker = segments[i].trim();
+                let field = segments[i + 1].trim();
                 match marker {
                     TITLE_MARKER => title = Some(field),
                     CATEGORY_MARKER => category = Some(field),
                     URL_MARKER => url = Some(field),
+                    TAGS_MARKER => tags = Some(field),
+                    DESCRIPTION_MARKER => description = Some(field),
+                    ADDED_MARKER => added = field.parse::<u64>().ok(),
+                    LAST_VISITED_MARKER => last_visited = field.parse::<u64>().ok(),
+                    VISIT_COUNT_MARKER => visit_count = field.parse::<u64>().ok(),
                     _ => {}
                 }
             }
             if let (Some(title), Some(category), Some(url)) = (title, category, url) {
-                return Some(Bookmark::new(
-                    title.to_string(),
-                    category.to_string(),
-                    url.to_string(),
-                ));
+                let bookmark =
+                    Bookmark::new(title.to_string(), category.to_string(), url.to_string());
+                return Some(
+                    bookmark
+                        .with_tags(Self::parse_tags(tags.unwrap_or_default()))
+                        .with_description(description.unwrap_or_default())
+                        .with_added(added)
+                        .with_visits(last_visited, visit_count.unwrap_or(0)),
+                );
             }
         }
         None
     }
+
+    // Called after a backslash, a backslash that starts no escape sequence is kept as it is
+    fn unescape(chars: &mut Peekable<Chars>) -> char {
+        match chars.peek().copied() {
+            // Files written before fields were escaped can end a field with a backslash
+            Some(SEGMENT_END) if !Self::closes_later(chars.clone()) => ESCAPE,
+            Some(SEGMENT_START | SEGMENT_END | ESCAPE) => chars.next().unwrap_or(ESCAPE),
+            _ => ESCAPE,
+        }
+    }
+
+    // Escaped fields always end with an unescaped brace before the next segment starts
+    fn closes_later(mut chars: Peekable<Chars>) -> bool {
+        chars.next();
+        while let Some(c) = chars.next() {
+            match c {
+                ESCAPE => {
+                    chars.next();
+                }
+                SEGMENT_END => return true,
+                SEGMENT_START | '\n' => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    // A bookmark is one line in the file, so line breaks in a field become spaces
+    fn single_line(field: String) -> String {
+        match field.contains(['\n', '\r']) {
+            true => field.replace(['\n', '\r'], " "),
+            false => field,
+        }
+    }
+
+    fn escape(field: Cow<str>) -> Cow<str> {
+        if !field.contains([SEGMENT_START, SEGMENT_END, ESCAPE]) {
+            return field;
+        }
+        let mut escaped = String::with_capacity(field.len() + 2);
+        for c in field.chars() {
+            match c {
+                SEGMENT_START | SEGMENT_END | ESCAPE => {
+                    escaped.push(ESCAPE);
+                    escaped.push(c);
+                }
+                c => escaped.push(c),
+            }
+        }
+        escaped.into()
+    }
 }
 
 #[cfg(test)]
@@ -175,6 +453,22 @@ mod tests {
                 "https://www.rust-lang.org/"
             )
         );
+
+        // Test that long fields are only truncated in menu lines
+        let long_title = "A title that is longer than the menu column allows".to_string();
+        let bookmark = Bookmark::new(
+            long_title.clone(),
+            "Programming".to_string(),
+            "https://www.rust-lang.org/".to_string(),
+        );
+        let line = bookmark.to_line(TITLE_MAX_LENGTH, 11);
+        assert_eq!(Bookmark::from_line(&line).unwrap().title(), long_title);
+        assert!(bookmark.details().contains(&long_title));
+        let menu_line = bookmark.to_menu_line(TITLE_MAX_LENGTH, 11);
+        assert!(menu_line.starts_with(&format!(
+            "{{T}}{{{}}} {{C}}",
+            &long_title[..TITLE_MAX_LENGTH]
+        )));
     }
 
     #[test]
@@ -185,4 +479,175 @@ mod tests {
         assert_eq!(bookmark.category(), default_bookmark.category());
         assert_eq!(bookmark.url(), default_bookmark.url());
     }
+
+    #[test]
+    fn test_bookmark_escaped_line() {
+        let bookmark = Bookmark::new(
+            "Rust {async} book".to_string(),
+            "Back\\slash }{".to_string(),
+            "https://example.com/{id}?q=\\{}".to_string(),
+        );
+
+        let formatted_line = bookmark.to_line(0, 0);
+        assert_eq!(
+            formatted_line,
+            "{T}{Rust \\{async\\} book} {C}{Back\\\\slash \\}\\{} {U}{https://example.com/\\{id\\}?q=\\\\\\{\\}}\n"
+        );
+
+        // Test that escaped fields round-trip unchanged
+        let parsed = Bookmark::from_line(&formatted_line).unwrap();
+        assert_eq!(parsed.title(), bookmark.title());
+        assert_eq!(parsed.category(), bookmark.category());
+        assert_eq!(parsed.url(), bookmark.url());
+
+        // Test that a backslash not followed by a special character is kept
+        let parsed =
+            Bookmark::from_line("{T}{C:\\Users} {C}{Files} {U}{file:///C:/Users}").unwrap();
+        assert_eq!(parsed.title(), "C:\\Users");
+        let line = "{T}{C:\\temp a\\nb \\x41 \\x1b} {C}{Files} {U}{file:///C:/temp}\n";
+        let parsed = Bookmark::from_line(line).unwrap();
+        assert_eq!(parsed.title(), "C:\\temp a\\nb \\x41 \\x1b");
+        let reparsed = Bookmark::from_line(&parsed.to_line(0, 0)).unwrap();
+        assert_eq!(reparsed.title(), parsed.title());
+
+        // Test that line breaks become spaces and a trailing backslash round-trips
+        let bookmark = Bookmark::new(
+            "first\nsecond\r\tthird\u{7}".to_string(),
+            "C:\\".to_string(),
+            "https://example.com/".to_string(),
+        )
+        .with_description("ends with \\");
+        assert_eq!(bookmark.title(), "first second \tthird\u{7}");
+        let formatted_line = bookmark.to_line(0, 0);
+        assert_eq!(formatted_line.lines().count(), 1);
+        assert!(formatted_line.contains("{T}{first second \tthird\u{7}}"));
+        assert!(formatted_line.contains("{D}{ends with \\\\}"));
+        assert!(Bookmark::from_line(&formatted_line).unwrap() == bookmark);
+
+        // Test that fields ending in a backslash from files written before escaping still parse
+        let parsed = Bookmark::from_line("{T}{C:\\} {C}{Windows\\} {U}{file:///C:/}").unwrap();
+        assert_eq!(parsed.title(), "C:\\");
+        assert_eq!(parsed.category(), "Windows\\");
+        assert_eq!(parsed.url(), "file:///C:/");
+    }
+
+    #[test]
+    fn test_bookmark_tags() {
+        let bookmark = Bookmark::default().with_tags(vec![
+            "rust".to_string(),
+            " async ".to_string(),
+            "".to_string(),
+            "rust".to_string(),
+        ]);
+        assert_eq!(bookmark.tags(), ["rust", "async"]);
+        assert!(bookmark.has_tag("async"));
+        assert!(!bookmark.has_tag("docs"));
+
+        let formatted_line = bookmark.to_line(0, 0);
+        assert!(formatted_line.ends_with(" {G}{rust,async}\n"));
+
+        // Test that tags round-trip unchanged
+        let parsed = Bookmark::from_line(&formatted_line).unwrap();
+        assert_eq!(parsed.tags(), bookmark.tags());
+
+        // Test that a line without tags still parses and writes no tags segment
+        let parsed = Bookmark::from_line(&Bookmark::default().to_line(0, 0)).unwrap();
+        assert!(parsed.tags().is_empty());
+        assert!(!parsed
+            .to_line(0, 0)
+            .contains(&format!("{{{}}}", TAGS_MARKER)));
+
+        assert_eq!(Bookmark::parse_tags("a, b,,c "), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_bookmark_description() {
+        let bookmark = Bookmark::default()
+            .with_tags(vec!["rust".to_string()])
+            .with_description(" Saved for the\n{escaping} docs ");
+        assert_eq!(bookmark.description(), "Saved for the {escaping} docs");
+
+        let formatted_line = bookmark.to_line(0, 0);
+        assert!(formatted_line.ends_with(" {G}{rust} {D}{Saved for the \\{escaping\\} docs}\n"));
+
+        // Test that the description round-trips unchanged
+        let parsed = Bookmark::from_line(&formatted_line).unwrap();
+        assert_eq!(parsed.description(), bookmark.description());
+        assert_eq!(parsed.tags(), bookmark.tags());
+
+        // Test that a line without a description still parses and writes no description segment
+        let parsed = Bookmark::from_line(&Bookmark::default().to_line(0, 0)).unwrap();
+        assert!(parsed.description().is_empty());
+        assert!(!parsed
+            .to_line(0, 0)
+            .contains(&format!("{{{}}}", DESCRIPTION_MARKER)));
+    }
+
+    #[test]
+    fn test_bookmark_details() {
+        let bookmark = Bookmark::new(
+            "The Rust Programming Language Book, Second Edition".to_string(),
+            "Programming".to_string(),
+            "https://doc.rust-lang.org/book/".to_string(),
+        );
+        assert_eq!(
+            bookmark.details(),
+            "Title:       The Rust Programming Language Book, Second Edition\n\
+             Category:    Programming\n\
+             URL:         https://doc.rust-lang.org/book/\n"
+        );
+
+        let bookmark = bookmark
+            .with_tags(vec!["rust".to_string(), "docs".to_string()])
+            .with_description("Read it twice");
+        assert!(bookmark
+            .details()
+            .ends_with("Tags:        rust, docs\nDescription: Read it twice\n"));
+    }
+
+    #[test]
+    fn test_bookmark_history() {
+        let mut bookmark = Bookmark::default().with_added(Some(100));
+        bookmark.visit(200);
+        bookmark.visit(300);
+        assert_eq!(bookmark.added(), Some(100));
+        assert_eq!(bookmark.last_visited(), Some(300));
+        assert_eq!(bookmark.visit_count(), 2);
+
+        let formatted_line = bookmark.to_line(0, 0);
+        assert!(formatted_line.ends_with(" {A}{100} {V}{300} {N}{2}\n"));
+
+        // Test that the history round-trips unchanged
+        let parsed = Bookmark::from_line(&formatted_line).unwrap();
+        assert!(parsed == bookmark);
+
+        // Test that the history can be carried over to a modified bookmark
+        let modified = Bookmark::new("a".to_string(), "b".to_string(), "c".to_string())
+            .with_history_of(&bookmark);
+        assert_eq!(modified.added(), Some(100));
+        assert_eq!(modified.last_visited(), Some(300));
+        assert_eq!(modified.visit_count(), 2);
+
+        // Test that a line without history still parses and writes no history segments
+        let parsed = Bookmark::from_line(&Bookmark::default().to_line(0, 0)).unwrap();
+        assert_eq!(parsed.added(), None);
+        assert_eq!(parsed.last_visited(), None);
+        assert_eq!(parsed.visit_count(), 0);
+        assert!(!parsed
+            .to_line(0, 0)
+            .contains(&format!("{{{}}}", ADDED_MARKER)));
+    }
+
+    #[test]
+    fn test_bookmark_frecency() {
+        let now = 1000 * DAY_IN_SECONDS;
+        assert_eq!(Bookmark::default().frecency(now), 0);
+
+        let recent = Bookmark::default().with_visits(Some(now - DAY_IN_SECONDS), 2);
+        let old = Bookmark::default().with_visits(Some(now - 200 * DAY_IN_SECONDS), 10);
+        let frequent_old = Bookmark::default().with_visits(Some(now - 200 * DAY_IN_SECONDS), 30);
+        assert_eq!(recent.frecency(now), 200);
+        assert_eq!(old.frecency(now), 100);
+        assert!(frequent_old.frecency(now) > recent.frecency(now));
+    }
 }
@@ -0,0 +1,316 @@
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+const MAX_REDIRECTS: &str = "10";
+const CHECKED_SCHEMES: [&str; 2] = ["http://", "https://"];
+const PERMANENT_REDIRECTS: [u16; 2] = [301, 308];
+// Servers that refuse HEAD requests are asked again with GET
+const HEAD_NOT_SUPPORTED: [u16; 3] = [403, 405, 501];
+
+pub enum LinkStatus {
+    Ok,
+    Redirected { location: String },
+    Broken { status: u16 },
+    Failed { reason: String },
+    Skipped,
+}
+
+impl LinkStatus {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Ok => "ok".to_string(),
+            Self::Redirected { location } => format!("moved permanently to {}", location),
+            Self::Broken { status } => format!("HTTP {}", status),
+            Self::Failed { reason } => reason.clone(),
+            Self::Skipped => "skipped".to_string(),
+        }
+    }
+}
+
+struct Response {
+    statuses: Vec<u16>,
+    effective_url: String,
+}
+
+pub fn check_urls(urls: &[&str], jobs: usize, timeout: u64) -> Vec<LinkStatus> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(urls.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, urls.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(url) = urls.get(index) else {
+                    break;
+                };
+                let status = check_url(url, timeout);
+                if let Ok(mut results) = results.lock() {
+                    results.push((index, status));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap_or_default();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, status)| status).collect()
+}
+
+pub fn check_url(url: &str, timeout: u64) -> LinkStatus {
+    let lowercase_url = url.to_ascii_lowercase();
+    if !CHECKED_SCHEMES
+        .iter()
+        .any(|scheme| lowercase_url.starts_with(scheme))
+    {
+        return LinkStatus::Skipped;
+    }
+
+    let mut response = request(url, timeout, true);
+    if let Ok(head_response) = &response {
+        if head_response
+            .statuses
+            .last()
+            .is_some_and(|status| HEAD_NOT_SUPPORTED.contains(status))
+        {
+            response = request(url, timeout, false);
+        }
+    }
+
+    classify(response)
+}
+
+fn classify(response: Result<Response, String>) -> LinkStatus {
+    match response {
+        Ok(response) => match (response.statuses.first(), response.statuses.last()) {
+            (_, Some(&status)) if status >= 400 => LinkStatus::Broken { status },
+            (Some(first), _) if PERMANENT_REDIRECTS.contains(first) => LinkStatus::Redirected {
+                location: response.effective_url,
+            },
+            (_, Some(_)) => LinkStatus::Ok,
+            (_, None) => LinkStatus::Failed {
+                reason: "no HTTP response".to_string(),
+            },
+        },
+        Err(reason) => LinkStatus::Failed { reason },
+    }
+}
+
+fn request(url: &str, timeout: u64, head: bool) -> Result<Response, String> {
+    let timeout = timeout.to_string();
+    let mut command = Command::new("curl");
+    command.args([
+        "--silent",
+        "--show-error",
+        "--location",
+        "--max-redirs",
+        MAX_REDIRECTS,
+        "--max-time",
+        &timeout,
+        "--output",
+        "/dev/null",
+        "--dump-header",
+        "-",
+        "--write-out",
+        "\n%{url_effective}",
+    ]);
+    if head {
+        command.arg("--head");
+    }
+    let output = command
+        .arg("--")
+        .arg(url)
+        .output()
+        .map_err(|_| "Failed to execute command: curl".to_string())?;
+
+    if !output.status.success() {
+        let reason = match output.status.code() {
+            Some(6) => "DNS lookup failed",
+            Some(7) => "connection failed",
+            Some(28) => "timed out",
+            Some(47) => "too many redirects",
+            Some(35 | 51 | 58 | 60) => "TLS error",
+            _ => "request failed",
+        };
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(match message.trim() {
+            "" => reason.to_string(),
+            message => format!("{} ({})", reason, message),
+        });
+    }
+
+    Ok(parse_response(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_response(stdout: &str) -> Response {
+    let (headers, effective_url) = stdout.rsplit_once('\n').unwrap_or(("", stdout));
+    let statuses = headers
+        .lines()
+        .filter(|line| line.starts_with("HTTP/"))
+        .filter_map(|line| line.split_whitespace().nth(1)?.parse::<u16>().ok())
+        .filter(|status| *status >= 200)
+        .collect();
+
+    Response {
+        statuses,
+        effective_url: effective_url.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn stub_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match stream.read(&mut buffer) {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => request.extend_from_slice(&buffer[..read]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let mut request_line = request.split_whitespace();
+                let method = request_line.next().unwrap_or("");
+                let path = request_line.next().unwrap_or("");
+                let (status, location) = match (method, path) {
+                    (_, "/ok") => ("200 OK", None),
+                    (_, "/moved") => ("301 Moved Permanently", Some("/ok")),
+                    (_, "/moved-to-missing") => ("308 Permanent Redirect", Some("/missing")),
+                    (_, "/found") => ("302 Found", Some("/ok")),
+                    (_, "/error") => ("500 Internal Server Error", None),
+                    ("HEAD", "/get-only") => ("405 Method Not Allowed", None),
+                    ("GET", "/get-only") => ("200 OK", None),
+                    _ => ("404 Not Found", None),
+                };
+                let location = location
+                    .map(|location| format!("Location: {}\r\n", location))
+                    .unwrap_or_default();
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+                    status, location
+                );
+            }
+        });
+        address
+    }
+
+    fn response(statuses: &[u16], effective_url: &str) -> Result<Response, String> {
+        Ok(Response {
+            statuses: statuses.to_vec(),
+            effective_url: effective_url.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_check_classify() {
+        assert!(matches!(
+            classify(response(&[200], "https://a.com/")),
+            LinkStatus::Ok
+        ));
+        assert!(matches!(
+            classify(response(&[302, 200], "https://a.com/b")),
+            LinkStatus::Ok
+        ));
+        match classify(response(&[301, 308, 200], "https://b.com/")) {
+            LinkStatus::Redirected { location } => assert_eq!(location, "https://b.com/"),
+            _ => panic!("expected a permanent redirect"),
+        }
+        // Test that a redirect to a broken page is reported as broken
+        assert!(matches!(
+            classify(response(&[308, 404], "https://b.com/")),
+            LinkStatus::Broken { status: 404 }
+        ));
+        assert!(matches!(
+            classify(response(&[500], "https://a.com/")),
+            LinkStatus::Broken { status: 500 }
+        ));
+        assert!(matches!(
+            classify(response(&[], "")),
+            LinkStatus::Failed { .. }
+        ));
+        match classify(Err("timed out".to_string())) {
+            LinkStatus::Failed { reason } => assert_eq!(reason, "timed out"),
+            _ => panic!("expected a failed request"),
+        }
+        assert!(matches!(
+            check_url("mailto:someone@example.com", 5),
+            LinkStatus::Skipped
+        ));
+    }
+
+    #[test]
+    fn test_check_parse_response() {
+        let stdout = "HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 301 Moved Permanently\r\n\
+                      Location: /ok\r\n\r\nHTTP/2 200\r\nServer: x\r\n\r\n\n\
+                      https://example.com/ok";
+        let response = parse_response(stdout);
+        assert_eq!(response.statuses, vec![301, 200]);
+        assert_eq!(response.effective_url, "https://example.com/ok");
+
+        let response = parse_response("\nhttps://example.com/");
+        assert!(response.statuses.is_empty());
+        assert_eq!(response.effective_url, "https://example.com/");
+    }
+
+    #[test]
+    #[ignore = "requires curl"]
+    fn test_check_urls() {
+        let address = stub_server();
+        let urls: Vec<String> = [
+            "/ok",
+            "/missing",
+            "/moved",
+            "/moved-to-missing",
+            "/found",
+            "/error",
+            "/get-only",
+        ]
+        .iter()
+        .map(|path| format!("{}{}", address, path))
+        .collect();
+        let mut urls: Vec<&str> = urls.iter().map(|url| url.as_str()).collect();
+        urls.push("mailto:someone@example.com");
+
+        let results = check_urls(&urls, 4, 5);
+        assert!(matches!(results[0], LinkStatus::Ok));
+        assert!(matches!(results[1], LinkStatus::Broken { status: 404 }));
+        match &results[2] {
+            LinkStatus::Redirected { location } => {
+                assert_eq!(location, &format!("{}/ok", address))
+            }
+            _ => panic!("expected a permanent redirect"),
+        }
+        assert!(matches!(results[3], LinkStatus::Broken { status: 404 }));
+        assert!(matches!(results[4], LinkStatus::Ok));
+        assert!(matches!(results[5], LinkStatus::Broken { status: 500 }));
+        assert!(matches!(results[6], LinkStatus::Ok));
+        assert!(matches!(results[7], LinkStatus::Skipped));
+    }
+
+    #[test]
+    #[ignore = "requires curl"]
+    fn test_check_url_failed() {
+        // Nothing listens on a port that was just released
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+
+        match check_url(&address, 5) {
+            LinkStatus::Failed { reason } => assert!(reason.starts_with("connection failed")),
+            _ => panic!("expected a failed request"),
+        }
+    }
+}
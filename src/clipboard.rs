@@ -0,0 +1,90 @@
+use std::process::Command;
+
+use crate::menu::Menu;
+use crate::url;
+
+const AUTO_CLIPBOARD: &str = "auto";
+const NO_CLIPBOARD: &str = "none";
+// Tried in order, the first one that succeeds is used
+const CLIPBOARD_PROGRAMS: [&[&str]; 3] = [
+    &["wl-paste", "--no-newline"],
+    &["xclip", "-selection", "clipboard", "-o"],
+    &["xsel", "--clipboard", "--output"],
+];
+
+pub enum Clipboard {
+    Auto,
+    Command(Vec<String>),
+    Disabled,
+}
+
+impl Clipboard {
+    pub fn new(command: &str) -> Result<Self, String> {
+        match command.trim() {
+            AUTO_CLIPBOARD => Ok(Self::Auto),
+            NO_CLIPBOARD | "" => Ok(Self::Disabled),
+            // Quotes keep arguments with spaces together
+            command => Ok(Self::Command(Menu::split_arguments(command)?)),
+        }
+    }
+
+    pub fn url(&self) -> Option<String> {
+        self.read()
+            .map(|contents| contents.trim().to_string())
+            .filter(|contents| url::looks_like_url(contents))
+    }
+
+    fn read(&self) -> Option<String> {
+        match self {
+            Self::Auto => CLIPBOARD_PROGRAMS
+                .iter()
+                .find_map(|command| Self::run_command(command)),
+            Self::Command(command) => Self::run_command(command),
+            Self::Disabled => None,
+        }
+    }
+
+    fn run_command<S: AsRef<str>>(command: &[S]) -> Option<String> {
+        let (program, args) = command.split_first()?;
+        let output = Command::new(program.as_ref())
+            .args(args.iter().map(|arg| arg.as_ref()))
+            .output()
+            .ok()?;
+        match output.status.success() {
+            true => Some(String::from_utf8_lossy(&output.stdout).to_string()),
+            false => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clipboard_url() {
+        assert_eq!(
+            Clipboard::new("echo https://example.com/")
+                .unwrap()
+                .url()
+                .as_deref(),
+            Some("https://example.com/")
+        );
+        assert_eq!(
+            Clipboard::new("sh -c 'echo https://example.com/quoted'")
+                .unwrap()
+                .url()
+                .as_deref(),
+            Some("https://example.com/quoted")
+        );
+
+        // Test with clipboard contents that are not a url
+        assert!(Clipboard::new("echo some copied text")
+            .unwrap()
+            .url()
+            .is_none());
+        assert!(Clipboard::new("false").unwrap().url().is_none());
+        assert!(Clipboard::new("none").unwrap().url().is_none());
+        assert!(Clipboard::new("sh -c 'echo").is_err());
+    }
+}
@@ -0,0 +1,403 @@
+use crate::bookmark::Bookmark;
+
+const DEFAULT_CATEGORY: &str = "Imported";
+const IGNORED_URL_SCHEMES: [&str; 2] = ["place:", "javascript:"];
+const HEADER: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file.
+     It will be read and overwritten.
+     DO NOT EDIT! -->
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+"#;
+
+pub fn parse(html: &str) -> Vec<Bookmark> {
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    let mut folders: Vec<String> = Vec::new();
+    let mut pending_folder: Option<String> = None;
+    let mut href: Option<String> = None;
+    let mut tags: Option<String> = None;
+    let mut added: Option<u64> = None;
+    let mut last_visited: Option<u64> = None;
+    let mut text = String::new();
+    let mut bookmark_pushed = false;
+    let mut describing = false;
+
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+
+        let name = tag
+            .split(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or("")
+            .to_ascii_uppercase();
+
+        if describing {
+            if let Some(bookmark) = bookmarks.pop() {
+                bookmarks.push(bookmark.with_description(&clean_text(&text)));
+            }
+            describing = false;
+        }
+        let previous_bookmark_pushed = std::mem::take(&mut bookmark_pushed);
+
+        match name.as_str() {
+            "H3" => text.clear(),
+            "DD" => {
+                describing = previous_bookmark_pushed;
+                text.clear();
+            }
+            "A" => {
+                href = attribute(tag, "HREF");
+                tags = attribute(tag, "TAGS");
+                added = attribute(tag, "ADD_DATE").and_then(|date| date.parse().ok());
+                last_visited = attribute(tag, "LAST_VISIT").and_then(|date| date.parse().ok());
+                text.clear();
+            }
+            "/H3" => pending_folder = Some(clean_text(&text)),
+            "/A" => {
+                if let Some(url) = href.take() {
+                    let url = url.trim();
+                    if url.is_empty() || IGNORED_URL_SCHEMES.iter().any(|s| url.starts_with(s)) {
+                        continue;
+                    }
+                    let title = match clean_text(&text) {
+                        title if title.is_empty() => url.to_string(),
+                        title => title,
+                    };
+                    let category = match folders.iter().rev().find(|f| !f.is_empty()) {
+                        Some(folder) => folder.clone(),
+                        None => DEFAULT_CATEGORY.to_string(),
+                    };
+                    let tags = Bookmark::parse_tags(&tags.take().unwrap_or_default());
+                    let visit_count = if last_visited.is_some() { 1 } else { 0 };
+                    let bookmark = Bookmark::new(title, category, url.to_string())
+                        .with_tags(tags)
+                        .with_added(added.take())
+                        .with_visits(last_visited.take(), visit_count);
+                    bookmarks.push(bookmark);
+                    bookmark_pushed = true;
+                }
+            }
+            "DL" => folders.push(pending_folder.take().unwrap_or_default()),
+            "/DL" => {
+                folders.pop();
+            }
+            _ => {}
+        }
+    }
+
+    bookmarks
+}
+
+pub fn render(sorted_bookmarks: &[&Bookmark]) -> String {
+    let mut html = String::from(HEADER);
+    html.push_str("<DL><p>\n");
+
+    // The sort interleaves categories that only differ in case, so group them into one folder each
+    let mut folders: Vec<(&str, Vec<&Bookmark>)> = Vec::new();
+    for bookmark in sorted_bookmarks {
+        match folders
+            .iter_mut()
+            .find(|(category, _)| *category == bookmark.category())
+        {
+            Some((_, bookmarks)) => bookmarks.push(bookmark),
+            None => folders.push((bookmark.category(), vec![bookmark])),
+        }
+    }
+
+    for (category, bookmarks) in folders {
+        html.push_str(&format!(
+            "    <DT><H3>{}</H3>\n    <DL><p>\n",
+            escape(category)
+        ));
+        for bookmark in bookmarks {
+            render_bookmark(&mut html, bookmark);
+        }
+        html.push_str("    </DL><p>\n");
+    }
+
+    html.push_str("</DL><p>\n");
+    html
+}
+
+fn render_bookmark(html: &mut String, bookmark: &Bookmark) {
+    let mut attributes = String::new();
+    if let Some(added) = bookmark.added() {
+        attributes.push_str(&format!(" ADD_DATE=\"{}\"", added));
+    }
+    if let Some(last_visited) = bookmark.last_visited() {
+        attributes.push_str(&format!(" LAST_VISIT=\"{}\"", last_visited));
+    }
+    if !bookmark.tags().is_empty() {
+        attributes.push_str(&format!(" TAGS=\"{}\"", escape(&bookmark.joined_tags())));
+    }
+    html.push_str(&format!(
+        "        <DT><A HREF=\"{}\"{}>{}</A>\n",
+        escape(bookmark.url()),
+        attributes,
+        escape(bookmark.title())
+    ));
+    if !bookmark.description().is_empty() {
+        html.push_str(&format!("        <DD>{}\n", escape(bookmark.description())));
+    }
+}
+
+pub fn title(html: &str) -> Option<String> {
+    let uppercase_html = html.to_ascii_uppercase();
+    let mut search_from = 0;
+    while let Some(index) = uppercase_html[search_from..].find("<TITLE") {
+        let start = search_from + index + "<TITLE".len();
+        search_from = start;
+        if !html[start..].starts_with(|c: char| c == '>' || c.is_whitespace()) {
+            continue;
+        }
+        let start = start + html[start..].find('>')? + 1;
+        let end = start + uppercase_html[start..].find("</TITLE")?;
+        let title = clean_text(&html[start..end]);
+        return (!title.is_empty()).then_some(title);
+    }
+    None
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| decode_entity(&rest[1..end]).map(|c| (c, end)));
+        match entity {
+            Some((c, end)) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code = entity.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse::<u32>().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+fn clean_text(text: &str) -> String {
+    unescape(text)
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let uppercase_tag = tag.to_ascii_uppercase();
+    let mut search_from = 0;
+    while let Some(index) = uppercase_tag[search_from..].find(name) {
+        let start = search_from + index;
+        search_from = start + name.len();
+        let preceded_by_space = tag[..start].ends_with(|c: char| c.is_whitespace());
+        let value = tag[search_from..].trim_start();
+        if !preceded_by_space || !value.starts_with('=') {
+            continue;
+        }
+        let value = value[1..].trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or(""),
+            _ => value
+                .split(|c: char| c.is_whitespace())
+                .next()
+                .unwrap_or(""),
+        };
+        return Some(unescape(value));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_parse() {
+        let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><A HREF="https://example.com/" ADD_DATE="1700000000" LAST_VISIT="1700000100">Top level</A>
+    <DT><H3 ADD_DATE="1">Development</H3>
+    <DD>Folder description
+    <DL><p>
+        <DT><A HREF="https://www.rust-lang.org/?a=1&amp;b=2" TAGS="rust,lang">Rust &amp; Cargo</A>
+        <DD>The &lt;Rust&gt;
+            language
+        <DT><H3>Docs</H3>
+        <DL><p>
+            <DT><a href='https://docs.rs/'>docs.rs</a>
+        </DL><p>
+        <DT><A HREF="https://crates.io/"></A>
+        <DT><A HREF="place:sort=8">Recent Tags</A>
+    </DL><p>
+</DL>
+"#;
+        let bookmarks = parse(html);
+        assert_eq!(bookmarks.len(), 4);
+
+        assert_eq!(bookmarks[0].title(), "Top level");
+        assert_eq!(bookmarks[0].category(), DEFAULT_CATEGORY);
+        assert_eq!(bookmarks[0].added(), Some(1700000000));
+        assert_eq!(bookmarks[0].last_visited(), Some(1700000100));
+        assert_eq!(bookmarks[1].added(), None);
+
+        assert_eq!(bookmarks[1].title(), "Rust & Cargo");
+        assert_eq!(bookmarks[1].category(), "Development");
+        assert_eq!(bookmarks[1].url(), "https://www.rust-lang.org/?a=1&b=2");
+        assert_eq!(bookmarks[1].tags(), ["rust", "lang"]);
+        assert!(bookmarks[2].tags().is_empty());
+        assert_eq!(bookmarks[1].description(), "The <Rust> language");
+        assert!(bookmarks[0].description().is_empty());
+        assert!(bookmarks[2].description().is_empty());
+
+        assert_eq!(bookmarks[2].category(), "Docs");
+        assert_eq!(bookmarks[2].url(), "https://docs.rs/");
+
+        // Test that an empty title falls back to the url
+        assert_eq!(bookmarks[3].title(), "https://crates.io/");
+        assert_eq!(bookmarks[3].category(), "Development");
+    }
+
+    #[test]
+    fn test_html_render() {
+        let bookmarks = [
+            Bookmark::new(
+                "Rust <Lang> & \"Cargo\"".to_string(),
+                "Development".to_string(),
+                "https://www.rust-lang.org/?a=1&b='2'".to_string(),
+            ),
+            Bookmark::new(
+                "docs.rs".to_string(),
+                "Development".to_string(),
+                "https://docs.rs/".to_string(),
+            )
+            .with_tags(vec!["rust".to_string(), "docs".to_string()])
+            .with_description("Docs & <more>")
+            .with_added(Some(1700000000))
+            .with_visits(Some(1700000100), 1),
+            Bookmark::new(
+                "News".to_string(),
+                "Reading".to_string(),
+                "https://news.ycombinator.com/".to_string(),
+            ),
+        ];
+        let sorted_bookmarks: Vec<&Bookmark> = bookmarks.iter().collect();
+        let html = render(&sorted_bookmarks);
+        assert!(html.starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>"));
+        assert!(html.contains("Rust &lt;Lang&gt; &amp; &quot;Cargo&quot;"));
+        assert!(html.contains("ADD_DATE=\"1700000000\" LAST_VISIT=\"1700000100\""));
+        assert_eq!(html.matches("<H3>").count(), 2);
+
+        // Test that rendered bookmarks parse back unchanged
+        let parsed = parse(&html);
+        assert_eq!(parsed.len(), bookmarks.len());
+        for (parsed, bookmark) in parsed.iter().zip(bookmarks.iter()) {
+            assert!(parsed == bookmark);
+        }
+
+        // Test that categories which only differ in case each get a single folder
+        let bookmark = |title: &str, category: &str| {
+            Bookmark::new(
+                title.to_string(),
+                category.to_string(),
+                format!("https://example.com/{}", title),
+            )
+        };
+        let bookmarks = [
+            bookmark("a", "Rust"),
+            bookmark("b", "rust"),
+            bookmark("c", "Rust"),
+            bookmark("d", "rust"),
+        ];
+        let sorted_bookmarks: Vec<&Bookmark> = bookmarks.iter().collect();
+        let html = render(&sorted_bookmarks);
+        assert_eq!(html.matches("<H3>Rust</H3>").count(), 1);
+        assert_eq!(html.matches("<H3>rust</H3>").count(), 1);
+        let parsed = parse(&html);
+        let categories: Vec<(&str, &str)> = parsed
+            .iter()
+            .map(|bookmark| (bookmark.title(), bookmark.category()))
+            .collect();
+        assert_eq!(
+            categories,
+            [("a", "Rust"), ("c", "Rust"), ("b", "rust"), ("d", "rust")]
+        );
+    }
+
+    #[test]
+    fn test_html_escape() {
+        assert_eq!(escape("a & b"), "a &amp; b");
+        assert_eq!(escape("<\"'>"), "&lt;&quot;&#39;&gt;");
+        assert_eq!(unescape(&escape("<a href='x'>&</a>")), "<a href='x'>&</a>");
+    }
+
+    #[test]
+    fn test_html_unescape() {
+        assert_eq!(unescape("a &amp; b"), "a & b");
+        assert_eq!(unescape("&lt;&gt;&quot;&#39;&#x41;"), "<>\"'A");
+        assert_eq!(unescape("fish & chips"), "fish & chips");
+        assert_eq!(unescape("&unknown;"), "&unknown;");
+    }
+
+    #[test]
+    fn test_html_title() {
+        let html = "<html><head><meta charset=\"utf-8\">\n<TITLE lang=\"en\">\n  Rust &amp; Cargo\n  Docs </title></head></html>";
+        assert_eq!(title(html).as_deref(), Some("Rust & Cargo Docs"));
+
+        // Test with pages without a usable title
+        assert!(title("<html><head><titles>x</titles></head></html>").is_none());
+        assert!(title("<title>   </title>").is_none());
+        assert!(title("<title>unterminated").is_none());
+    }
+}
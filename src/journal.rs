@@ -0,0 +1,301 @@
+use crate::bookmark::Bookmark;
+
+const MAX_JOURNAL_CHANGES: usize = 100;
+const UNDO_HEADER: &str = "undo";
+const REDO_HEADER: &str = "redo";
+const REMOVED_PREFIX: &str = "- ";
+const ADDED_PREFIX: &str = "+ ";
+// Modifications are written as a pair of lines
+const MODIFIED_BEFORE_PREFIX: &str = "~ ";
+const MODIFIED_AFTER_PREFIX: &str = "> ";
+
+#[derive(Clone, PartialEq)]
+pub struct Edit {
+    before: Option<Bookmark>,
+    after: Option<Bookmark>,
+}
+
+impl Edit {
+    pub fn before(&self) -> Option<&Bookmark> {
+        self.before.as_ref()
+    }
+
+    pub fn after(&self) -> Option<&Bookmark> {
+        self.after.as_ref()
+    }
+}
+
+#[derive(Clone)]
+pub struct Journal {
+    undo: Vec<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    current: Vec<Edit>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            current: Vec::new(),
+        }
+    }
+
+    pub fn parse(journal: &str) -> Self {
+        let mut parsed_journal = Self::new();
+        let mut stack: Option<&mut Vec<Vec<Edit>>> = None;
+        let mut modified_before = None;
+
+        for line in journal.lines() {
+            match line {
+                UNDO_HEADER => {
+                    parsed_journal.undo.push(Vec::new());
+                    stack = Some(&mut parsed_journal.undo);
+                }
+                REDO_HEADER => {
+                    parsed_journal.redo.push(Vec::new());
+                    stack = Some(&mut parsed_journal.redo);
+                }
+                _ => {
+                    let Some(edits) = stack.as_mut().and_then(|stack| stack.last_mut()) else {
+                        continue;
+                    };
+                    if let Some(before) = line.strip_prefix(REMOVED_PREFIX) {
+                        edits.push(Edit {
+                            before: Bookmark::from_line(before),
+                            after: None,
+                        });
+                    } else if let Some(after) = line.strip_prefix(ADDED_PREFIX) {
+                        edits.push(Edit {
+                            before: None,
+                            after: Bookmark::from_line(after),
+                        });
+                    } else if let Some(before) = line.strip_prefix(MODIFIED_BEFORE_PREFIX) {
+                        modified_before = Bookmark::from_line(before);
+                    } else if let Some(after) = line.strip_prefix(MODIFIED_AFTER_PREFIX) {
+                        // An after line without its before line can not be undone
+                        if let Some(before) = modified_before.take() {
+                            edits.push(Edit {
+                                before: Some(before),
+                                after: Bookmark::from_line(after),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        parsed_journal.undo.retain(|edits| !edits.is_empty());
+        parsed_journal.redo.retain(|edits| !edits.is_empty());
+        parsed_journal
+    }
+
+    // Puts the edits made since `base` was read on top of the journal another writer saved
+    pub fn merge(mut self, base: &Journal, theirs: Journal) -> Self {
+        let kept = self
+            .undo
+            .iter()
+            .zip(&base.undo)
+            .take_while(|(ours, base)| ours == base)
+            .count();
+        let undone = &base.undo[kept..];
+        let mut undo: Vec<Vec<Edit>> = theirs
+            .undo
+            .into_iter()
+            .filter(|edits| !undone.contains(edits))
+            .collect();
+        undo.extend(self.undo.drain(kept..));
+        let redo = match self.redo == base.redo {
+            true => theirs.redo,
+            false => self.redo,
+        };
+        Self {
+            undo,
+            redo,
+            current: self.current,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut journal = String::new();
+        let undo_start = self.undo.len().saturating_sub(MAX_JOURNAL_CHANGES);
+        for (header, stack) in [
+            (UNDO_HEADER, &self.undo[undo_start..]),
+            (REDO_HEADER, &self.redo[..]),
+        ] {
+            for edits in stack {
+                journal.push_str(&format!("{}\n", header));
+                for edit in edits {
+                    let (before_prefix, after_prefix) = match (&edit.before, &edit.after) {
+                        (Some(_), Some(_)) => (MODIFIED_BEFORE_PREFIX, MODIFIED_AFTER_PREFIX),
+                        _ => (REMOVED_PREFIX, ADDED_PREFIX),
+                    };
+                    if let Some(before) = &edit.before {
+                        journal.push_str(&format!("{}{}", before_prefix, before.to_line(0, 0)));
+                    }
+                    if let Some(after) = &edit.after {
+                        journal.push_str(&format!("{}{}", after_prefix, after.to_line(0, 0)));
+                    }
+                }
+            }
+        }
+        journal
+    }
+
+    pub fn record(&mut self, before: Option<&Bookmark>, after: Option<&Bookmark>) {
+        self.current.push(Edit {
+            before: before.cloned(),
+            after: after.cloned(),
+        });
+    }
+
+    pub fn commit(&mut self) {
+        if !self.current.is_empty() {
+            self.undo.push(std::mem::take(&mut self.current));
+            self.redo.clear();
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || !self.current.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty() && self.current.is_empty()
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Vec<Edit>> {
+        self.commit();
+        self.undo.pop()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Vec<Edit>> {
+        self.commit();
+        self.redo.pop()
+    }
+
+    pub fn push_undo(&mut self, edits: Vec<Edit>) {
+        if !edits.is_empty() {
+            self.undo.push(edits);
+        }
+    }
+
+    pub fn push_redo(&mut self, edits: Vec<Edit>) {
+        if !edits.is_empty() {
+            self.redo.push(edits);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.current.clear();
+    }
+
+    pub fn take_current(&mut self) -> Vec<Edit> {
+        std::mem::take(&mut self.current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(title: &str, url: &str) -> Bookmark {
+        Bookmark::new(title.to_string(), "category".to_string(), url.to_string())
+    }
+
+    #[test]
+    fn test_journal_commit() {
+        let mut journal = Journal::new();
+        assert!(!journal.can_undo());
+
+        journal.record(None, Some(&bookmark("a", "url_a")));
+        assert!(journal.can_undo());
+        journal.commit();
+        journal.push_redo(Vec::new());
+        assert!(!journal.can_redo());
+        journal.push_redo(vec![Edit {
+            before: None,
+            after: None,
+        }]);
+        assert!(journal.can_redo());
+
+        // Test that a new change clears the redo stack
+        journal.record(Some(&bookmark("a", "url_a")), None);
+        assert!(!journal.can_redo());
+        journal.commit();
+        assert!(journal.redo.is_empty());
+        assert_eq!(journal.undo.len(), 2);
+
+        let edits = journal.pop_undo().unwrap();
+        assert!(edits[0].after().is_none());
+        assert_eq!(edits[0].before().unwrap().url(), "url_a");
+    }
+
+    #[test]
+    fn test_journal_render() {
+        let mut journal = Journal::new();
+        journal.record(None, Some(&bookmark("a", "url_a")));
+        journal.record(Some(&bookmark("a", "url_a")), Some(&bookmark("b", "url_a")));
+        journal.commit();
+        journal.record(Some(&bookmark("b", "url_a")), None);
+        journal.commit();
+        let edits = journal.pop_undo().unwrap();
+        journal.push_redo(edits);
+
+        let rendered = journal.render();
+        assert_eq!(rendered.lines().filter(|line| *line == "undo").count(), 1);
+        assert_eq!(rendered.lines().filter(|line| *line == "redo").count(), 1);
+
+        // Test that rendered journals parse back unchanged
+        let parsed = Journal::parse(&rendered);
+        assert_eq!(parsed.undo.len(), 1);
+        assert_eq!(parsed.undo[0].len(), 2);
+        assert!(parsed.undo[0][0].before().is_none());
+        assert_eq!(parsed.undo[0][1].before().unwrap().title(), "a");
+        assert_eq!(parsed.undo[0][1].after().unwrap().title(), "b");
+        assert_eq!(parsed.redo.len(), 1);
+        assert!(parsed.redo[0][0].after().is_none());
+        assert_eq!(parsed.render(), rendered);
+
+        // Test that a removal followed by an addition stays two edits
+        let mut journal = Journal::new();
+        journal.record(Some(&bookmark("a", "url_a")), None);
+        journal.record(None, Some(&bookmark("b", "url_b")));
+        journal.commit();
+        let parsed = Journal::parse(&journal.render());
+        assert_eq!(parsed.undo[0].len(), 2);
+        assert!(parsed.undo[0][0].after().is_none());
+        assert!(parsed.undo[0][1].before().is_none());
+        assert_eq!(parsed.undo[0][1].after().unwrap().url(), "url_b");
+    }
+
+    #[test]
+    fn test_journal_merge() {
+        let mut base = Journal::new();
+        base.record(None, Some(&bookmark("a", "url_a")));
+        base.commit();
+        base.record(None, Some(&bookmark("b", "url_b")));
+        base.commit();
+
+        // Another writer adds c, we undo b and add d
+        let mut theirs = base.clone();
+        theirs.record(None, Some(&bookmark("c", "url_c")));
+        theirs.commit();
+        let mut ours = base.clone();
+        let undone = ours.pop_undo().unwrap();
+        ours.push_redo(undone);
+        ours.record(None, Some(&bookmark("d", "url_d")));
+
+        let merged = ours.merge(&base, theirs);
+        let urls: Vec<&str> = merged
+            .undo
+            .iter()
+            .map(|edits| edits[0].after().unwrap().url())
+            .collect();
+        assert_eq!(urls, ["url_a", "url_c"]);
+        assert_eq!(merged.redo.len(), 1);
+        assert_eq!(merged.current[0].after().unwrap().url(), "url_d");
+    }
+}
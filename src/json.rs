@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::bookmark::Bookmark;
+
+const TITLE_KEY: &str = "title";
+const CATEGORY_KEY: &str = "category";
+const URL_KEY: &str = "url";
+const TAGS_KEY: &str = "tags";
+const DESCRIPTION_KEY: &str = "description";
+const ADDED_KEY: &str = "added";
+const LAST_VISITED_KEY: &str = "last_visited";
+const VISIT_COUNT_KEY: &str = "visit_count";
+// Deeper nesting is refused instead of overflowing the stack
+const MAX_DEPTH: usize = 512;
+
+enum Value {
+    Null,
+    Bool,
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(HashMap<String, Value>),
+}
+
+pub fn parse(json: &str) -> Result<Vec<Bookmark>, String> {
+    let mut chars = json.chars().peekable();
+    let value = parse_value(&mut chars, 0)?;
+    skip_whitespace(&mut chars);
+    if chars.peek().is_some() {
+        return Err("Invalid JSON: unexpected trailing characters".to_string());
+    }
+
+    let items = match value {
+        Value::Array(items) => items,
+        _ => return Err("Invalid JSON: expected an array of bookmarks".to_string()),
+    };
+
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let mut object = match item {
+                Value::Object(object) => object,
+                _ => return Err(format!("Invalid JSON: bookmark {} is not an object", i)),
+            };
+            let mut field = |key: &str| match object.remove(key) {
+                Some(Value::String(value)) => Ok(value),
+                _ => Err(format!(
+                    "Invalid JSON: bookmark {} is missing the string field '{}'",
+                    i, key
+                )),
+            };
+            let bookmark = Bookmark::new(field(TITLE_KEY)?, field(CATEGORY_KEY)?, field(URL_KEY)?);
+            let tags = match object.remove(TAGS_KEY) {
+                Some(Value::Array(tags)) => tags
+                    .into_iter()
+                    .map(|tag| match tag {
+                        Value::String(tag) => Ok(tag),
+                        _ => Err(format!(
+                            "Invalid JSON: bookmark {} has a tag that is not a string",
+                            i
+                        )),
+                    })
+                    .collect::<Result<Vec<String>, String>>()?,
+                Some(Value::Null) | None => Vec::new(),
+                Some(_) => {
+                    return Err(format!(
+                        "Invalid JSON: bookmark {} has a '{}' field that is not an array",
+                        i, TAGS_KEY
+                    ))
+                }
+            };
+            let description = match object.remove(DESCRIPTION_KEY) {
+                Some(Value::String(description)) => description,
+                Some(Value::Null) | None => String::new(),
+                Some(_) => {
+                    return Err(format!(
+                        "Invalid JSON: bookmark {} has a '{}' field that is not a string",
+                        i, DESCRIPTION_KEY
+                    ))
+                }
+            };
+            let mut timestamp = |key: &str| match object.remove(key) {
+                Some(Value::Number(number)) if number >= 0.0 => Ok(Some(number as u64)),
+                Some(Value::Null) | None => Ok(None),
+                Some(_) => Err(format!(
+                    "Invalid JSON: bookmark {} has a '{}' field that is not a positive number",
+                    i, key
+                )),
+            };
+            let added = timestamp(ADDED_KEY)?;
+            let last_visited = timestamp(LAST_VISITED_KEY)?;
+            let visit_count = timestamp(VISIT_COUNT_KEY)?;
+            Ok(bookmark
+                .with_tags(tags)
+                .with_description(&description)
+                .with_added(added)
+                .with_visits(last_visited, visit_count.unwrap_or(0)))
+        })
+        .collect()
+}
+
+pub fn render(sorted_bookmarks: &[&Bookmark]) -> String {
+    if sorted_bookmarks.is_empty() {
+        return "[]\n".to_string();
+    }
+
+    let objects: Vec<String> = sorted_bookmarks
+        .iter()
+        .map(|bookmark| {
+            let tags = bookmark
+                .tags()
+                .iter()
+                .map(|tag| escape(tag))
+                .collect::<Vec<String>>()
+                .join(", ");
+            format!(
+                "  {{\n    \"{}\": {},\n    \"{}\": {},\n    \"{}\": {},\n    \"{}\": [{}],\n    \"{}\": {},\n    \"{}\": {},\n    \"{}\": {},\n    \"{}\": {}\n  }}",
+                TITLE_KEY,
+                escape(bookmark.title()),
+                CATEGORY_KEY,
+                escape(bookmark.category()),
+                URL_KEY,
+                escape(bookmark.url()),
+                TAGS_KEY,
+                tags,
+                DESCRIPTION_KEY,
+                escape(bookmark.description()),
+                ADDED_KEY,
+                optional_number(bookmark.added()),
+                LAST_VISITED_KEY,
+                optional_number(bookmark.last_visited()),
+                VISIT_COUNT_KEY,
+                bookmark.visit_count()
+            )
+        })
+        .collect();
+
+    format!("[\n{}\n]\n", objects.join(",\n"))
+}
+
+fn optional_number(number: Option<u64>) -> String {
+    match number {
+        Some(number) => number.to_string(),
+        None => "null".to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            _ => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<(), String> {
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        Some(c) => Err(format!(
+            "Invalid JSON: expected '{}', found '{}'",
+            expected, c
+        )),
+        None => Err(format!(
+            "Invalid JSON: expected '{}', found end of input",
+            expected
+        )),
+    }
+}
+
+fn expect_word(chars: &mut Peekable<Chars>, word: &str) -> Result<(), String> {
+    for expected in word.chars() {
+        expect(chars, expected)?;
+    }
+    Ok(())
+}
+
+fn parse_value(chars: &mut Peekable<Chars>, depth: usize) -> Result<Value, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('{' | '[') if depth >= MAX_DEPTH => Err("JSON nested too deeply".to_string()),
+        Some('{') => parse_object(chars, depth + 1),
+        Some('[') => parse_array(chars, depth + 1),
+        Some('"') => parse_string(chars).map(Value::String),
+        Some('t') => expect_word(chars, "true").map(|_| Value::Bool),
+        Some('f') => expect_word(chars, "false").map(|_| Value::Bool),
+        Some('n') => expect_word(chars, "null").map(|_| Value::Null),
+        Some(c) if *c == '-' || c.is_ascii_digit() => parse_number(chars),
+        Some(c) => Err(format!("Invalid JSON: unexpected character '{}'", c)),
+        None => Err("Invalid JSON: unexpected end of input".to_string()),
+    }
+}
+
+fn parse_object(chars: &mut Peekable<Chars>, depth: usize) -> Result<Value, String> {
+    expect(chars, '{')?;
+    let mut object = HashMap::new();
+    skip_whitespace(chars);
+    if chars.next_if_eq(&'}').is_some() {
+        return Ok(Value::Object(object));
+    }
+    loop {
+        skip_whitespace(chars);
+        let key = parse_string(chars)?;
+        skip_whitespace(chars);
+        expect(chars, ':')?;
+        object.insert(key, parse_value(chars, depth)?);
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') => return Ok(Value::Object(object)),
+            _ => return Err("Invalid JSON: expected ',' or '}' in object".to_string()),
+        }
+    }
+}
+
+fn parse_array(chars: &mut Peekable<Chars>, depth: usize) -> Result<Value, String> {
+    expect(chars, '[')?;
+    let mut array = Vec::new();
+    skip_whitespace(chars);
+    if chars.next_if_eq(&']').is_some() {
+        return Ok(Value::Array(array));
+    }
+    loop {
+        array.push(parse_value(chars, depth)?);
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some(']') => return Ok(Value::Array(array)),
+            _ => return Err("Invalid JSON: expected ',' or ']' in array".to_string()),
+        }
+    }
+}
+
+fn parse_number(chars: &mut Peekable<Chars>) -> Result<Value, String> {
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+        number.push(c);
+    }
+    number
+        .parse::<f64>()
+        .map(Value::Number)
+        .map_err(|_| format!("Invalid JSON: invalid number '{}'", number))
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    expect(chars, '"')?;
+    let mut string = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => match chars.next() {
+                Some('"') => string.push('"'),
+                Some('\\') => string.push('\\'),
+                Some('/') => string.push('/'),
+                Some('b') => string.push('\u{8}'),
+                Some('f') => string.push('\u{c}'),
+                Some('n') => string.push('\n'),
+                Some('r') => string.push('\r'),
+                Some('t') => string.push('\t'),
+                Some('u') => string.push(parse_unicode_escape(chars)?),
+                _ => return Err("Invalid JSON: invalid escape sequence".to_string()),
+            },
+            Some(c) => string.push(c),
+            None => return Err("Invalid JSON: unterminated string".to_string()),
+        }
+    }
+}
+
+fn parse_unicode_escape(chars: &mut Peekable<Chars>) -> Result<char, String> {
+    let read_code_unit = |chars: &mut Peekable<Chars>| -> Result<u32, String> {
+        let hex: String = chars.by_ref().take(4).collect();
+        u32::from_str_radix(&hex, 16)
+            .map_err(|_| format!("Invalid JSON: invalid unicode escape '\\u{}'", hex))
+    };
+
+    let high = read_code_unit(chars)?;
+    let code = if (0xD800..0xDC00).contains(&high) {
+        expect(chars, '\\')?;
+        expect(chars, 'u')?;
+        let low = read_code_unit(chars)?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err("Invalid JSON: invalid surrogate pair".to_string());
+        }
+        0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+    } else {
+        high
+    };
+
+    char::from_u32(code).ok_or_else(|| "Invalid JSON: invalid unicode escape".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_parse() {
+        let json = r#"[
+            {"title": "Rust \"Lang\"", "category": "Development", "url": "https://www.rust-lang.org/", "added": 1700000000, "last_visited": null, "visit_count": 3},
+            {"url": "https://docs.rs/", "category": "Docs", "title": "docs.rs é 🦀", "tags": ["rust", "docs"], "description": "Crate \u0064ocs", "extra": [null, true, {}]}
+        ]"#;
+        let bookmarks = parse(json).unwrap();
+        assert_eq!(bookmarks.len(), 2);
+        assert_eq!(bookmarks[0].title(), "Rust \"Lang\"");
+        assert_eq!(bookmarks[0].category(), "Development");
+        assert_eq!(bookmarks[0].url(), "https://www.rust-lang.org/");
+        assert_eq!(bookmarks[1].title(), "docs.rs é 🦀");
+        assert!(bookmarks[0].tags().is_empty());
+        assert_eq!(bookmarks[1].tags(), ["rust", "docs"]);
+        assert!(bookmarks[0].description().is_empty());
+        assert_eq!(bookmarks[1].description(), "Crate docs");
+        assert_eq!(bookmarks[0].added(), Some(1700000000));
+        assert_eq!(bookmarks[0].last_visited(), None);
+        assert_eq!(bookmarks[0].visit_count(), 3);
+        assert_eq!(bookmarks[1].added(), None);
+
+        // Test with invalid documents
+        assert!(parse("").is_err());
+        assert!(parse("{}").is_err());
+        assert!(parse("[{\"title\": \"a\", \"category\": \"b\"}]").is_err());
+        assert!(parse("[{\"title\": \"a\", \"category\": \"b\", \"url\": 1}]").is_err());
+        assert!(parse("[] []").is_err());
+        assert!(
+            parse("[{\"title\": \"a\", \"category\": \"b\", \"url\": \"c\", \"added\": -1}]")
+                .is_err()
+        );
+        assert!(parse(
+            "[{\"title\": \"a\", \"category\": \"b\", \"url\": \"c\", \"tags\": \"d\"}]"
+        )
+        .is_err());
+        assert!(parse("[\"unterminated]").is_err());
+
+        // Test that deeply nested input is refused instead of overflowing the stack
+        let nested = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert_eq!(parse(&nested).err().unwrap(), "JSON nested too deeply");
+        let nested = format!(
+            "[{}{}]",
+            "[".repeat(MAX_DEPTH - 1),
+            "]".repeat(MAX_DEPTH - 1)
+        );
+        assert_ne!(parse(&nested).err().unwrap(), "JSON nested too deeply");
+    }
+
+    #[test]
+    fn test_json_render() {
+        let bookmarks = [
+            Bookmark::new(
+                "Rust \"Lang\" \\ {}".to_string(),
+                "Development".to_string(),
+                "https://www.rust-lang.org/".to_string(),
+            ),
+            Bookmark::new(
+                "Tab\tand\nnewline".to_string(),
+                "Docs".to_string(),
+                "https://docs.rs/".to_string(),
+            )
+            .with_tags(vec!["rust".to_string(), "\"quoted\"".to_string()])
+            .with_description("Docs for \"every\" crate")
+            .with_added(Some(1700000000))
+            .with_visits(Some(1700000100), 4),
+        ];
+        let sorted_bookmarks: Vec<&Bookmark> = bookmarks.iter().collect();
+        let json = render(&sorted_bookmarks);
+        assert!(json.contains(r#""title": "Rust \"Lang\" \\ {}""#));
+
+        // Test that rendered bookmarks parse back unchanged
+        let parsed = parse(&json).unwrap();
+        assert_eq!(parsed.len(), bookmarks.len());
+        for (parsed, bookmark) in parsed.iter().zip(bookmarks.iter()) {
+            assert!(parsed == bookmark);
+        }
+
+        assert_eq!(render(&[]), "[]\n");
+        assert!(parse(&render(&[])).unwrap().is_empty());
+    }
+}
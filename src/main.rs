@@ -1,20 +1,36 @@
 mod arguments;
+mod backup;
 mod bookmark;
+mod check;
+mod clipboard;
+mod html;
+mod journal;
+mod json;
 mod menu;
 mod parsed_file;
 mod plain_text;
+mod subcommand;
+mod title;
+mod tui;
+mod url;
 
 use arguments::Arguments;
+use backup::Backups;
 use bookmark::Bookmark;
+use clipboard::Clipboard;
 use menu::*;
-use parsed_file::ParsedFile;
+use parsed_file::{OnDuplicate, ParsedFile};
 use plain_text::PlainText;
 
+use std::collections::HashMap;
 use std::error::Error;
 use std::process::Command;
 
 pub const SEPARATOR_LINE_SYMBOL: &str = "-";
 pub const ADD_BOOKMARK: &str = "-| Add Bookmark |-";
+pub const FILTER_BY_TAG: &str = "-| Filter by Tag |-";
+pub const UNDO_LAST_CHANGE: &str = "-| Undo Last Change |-";
+pub const REDO_LAST_CHANGE: &str = "-| Redo Last Change |-";
 pub const TITLE_MAX_LENGTH: usize = 35;
 pub const CATEGORY_MAX_LENGTH: usize = 35;
 
@@ -24,20 +40,62 @@ const OPTIONS_REMOVE: &str = "remove";
 const OPTIONS_CANCEL: &str = "cancel";
 const OPTIONS: &str = "goto\nmodify\nremove\ncancel";
 
+const DUPLICATE_KEEP_BOTH: &str = "keep both";
+const DUPLICATE_MERGE: &str = "merge";
+const DUPLICATE_OPTIONS: &str = "keep both\nmerge\nskip";
+
+const REMOVE_YES: &str = "yes";
+const REMOVE_OPTIONS: &str = "no\nyes";
+
 const TITLE: &str = "title";
 const URL: &str = "url";
 const CATEGORY: &str = "category";
+const TAGS: &str = "tags (comma separated)";
+const TAG: &str = "tag";
+const DESCRIPTION: &str = "description";
 
 fn main() -> Result<(), Box<dyn Error>> {
     let arguments = Arguments::new()?;
 
+    let backups = Backups::new(
+        &arguments.bookmark_file_path,
+        arguments.backup_directory,
+        arguments.backup_count,
+    );
     let mut plain_text = PlainText::new(arguments.bookmark_file_path);
+    plain_text.set_sort_order(arguments.sort_order);
+    plain_text.set_backups(backups);
     plain_text.read()?;
 
     let mut parsed_file = ParsedFile::new(plain_text.bookmarks());
+    if !parsed_file.duplicates().is_empty() {
+        eprintln!(
+            "Warning: these URLs are bookmarked more than once, all copies were kept: {}. Use 'fmark dedupe' to review them.",
+            parsed_file.duplicate_urls().join(", ")
+        );
+    }
 
-    let menu = Menu::new(arguments.menu_program, arguments.menu_rows)?;
-    show_list(&mut plain_text, &mut parsed_file, menu, arguments.browser)?;
+    match arguments.subcommand {
+        Some(subcommand) => subcommand.run(&mut plain_text, &mut parsed_file)?,
+        None => {
+            let menu = Menu::new(
+                arguments.menu_program,
+                arguments.menu_rows,
+                arguments.ignore_case,
+            )?;
+            // Changes made before the menu program failed are still saved
+            let result = show_list(
+                &mut plain_text,
+                &mut parsed_file,
+                menu,
+                arguments.browser,
+                arguments.fetch_title,
+                arguments.clipboard,
+            );
+            plain_text.write(&parsed_file)?;
+            return Ok(result?);
+        }
+    }
 
     plain_text.write(&parsed_file)?;
 
@@ -49,37 +107,158 @@ fn show_list(
     parsed_file: &mut ParsedFile,
     menu: Menu,
     browser: String,
+    fetch_title: bool,
+    clipboard: Clipboard,
 ) -> Result<(), String> {
-    let add_bookmark_option_string = parsed_file.add_bookmark_option_string();
+    plain_text.journal_mut().commit();
 
-    plain_text.update_bookmarks(parsed_file);
-    let bookmarks_list = Some(plain_text.bookmarks());
-    let file_line = menu.choose(
-        bookmarks_list,
-        Some(&add_bookmark_option_string),
-        "bookmarks",
-    )?;
+    let add_bookmark_option_string = parsed_file.add_bookmark_option_string();
+    let filter_by_tag_option_string = parsed_file.filter_by_tag_option_string();
+    let undo_option_string = parsed_file.undo_option_string();
+    let redo_option_string = parsed_file.redo_option_string();
+
+    let menu_bookmarks = plain_text.menu_bookmarks(parsed_file);
+    let mut bookmarks_list = menu_bookmarks.clone();
+    if !parsed_file.tags().is_empty() {
+        bookmarks_list.push_str(&format!("{}\n", filter_by_tag_option_string));
+    }
+    if plain_text.journal().can_undo() {
+        bookmarks_list.push_str(&format!("{}\n", undo_option_string));
+    }
+    if plain_text.journal().can_redo() {
+        bookmarks_list.push_str(&format!("{}\n", redo_option_string));
+    }
+    let previews = match menu.shows_previews() {
+        true => PlainText::menu_previews(parsed_file),
+        false => HashMap::new(),
+    };
+    let mut file_line = menu
+        .choose_with_previews(
+            Some(&bookmarks_list),
+            Some(&add_bookmark_option_string),
+            "bookmarks",
+            &previews,
+        )?
+        .text()
+        .unwrap_or_default();
     if file_line.is_empty() {
         return Ok(());
     }
 
-    if let Some(bookmark) = Bookmark::from_line(&file_line) {
-        let option = menu.choose(Some(OPTIONS), None, "options")?;
-        if option.is_empty() {
-            show_list(plain_text, parsed_file, menu, browser)?;
+    if file_line == undo_option_string || file_line == redo_option_string {
+        let result = match file_line == undo_option_string {
+            true => parsed_file.undo(plain_text),
+            false => parsed_file.redo(plain_text),
+        };
+        if let Err(error) = result {
+            menu.choose(None, None, &error)?;
+        }
+        show_list(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
+        return Ok(());
+    }
+
+    if file_line == filter_by_tag_option_string {
+        let tags = parsed_file.tags().join("\n");
+        let tag = menu
+            .choose(Some(&tags), None, TAG)?
+            .text()
+            .unwrap_or_default();
+        if tag.is_empty() {
+            show_list(
+                plain_text,
+                parsed_file,
+                menu,
+                browser,
+                fetch_title,
+                clipboard,
+            )?;
             return Ok(());
         }
+        let tagged_list = PlainText::lines_with_tag(&menu_bookmarks, &tag);
+        file_line = menu
+            .choose_with_previews(Some(&tagged_list), None, &tag, &previews)?
+            .text()
+            .unwrap_or_default();
+        if file_line.is_empty() {
+            show_list(
+                plain_text,
+                parsed_file,
+                menu,
+                browser,
+                fetch_title,
+                clipboard,
+            )?;
+            return Ok(());
+        }
+    }
+
+    let bookmark = Bookmark::from_line(&file_line)
+        .and_then(|bookmark| parsed_file.find_menu_bookmark(&bookmark))
+        .cloned();
+    if let Some(bookmark) = bookmark {
+        // Text that is not one of the options is treated like cancel
+        let Choice::Selected(option) = menu.choose(Some(OPTIONS), None, "options")? else {
+            show_list(
+                plain_text,
+                parsed_file,
+                menu,
+                browser,
+                fetch_title,
+                clipboard,
+            )?;
+            return Ok(());
+        };
         match option.as_str() {
-            OPTIONS_GOTO => goto(browser, bookmark.url())?,
-            OPTIONS_MODIFY => modify(plain_text, parsed_file, menu, browser, bookmark)?,
-            OPTIONS_REMOVE => remove(plain_text, parsed_file, menu, browser, bookmark)?,
+            OPTIONS_GOTO => {
+                goto(browser, bookmark.url())?;
+                parsed_file.visit_bookmark(plain_text, &bookmark);
+            }
+            OPTIONS_MODIFY => modify(
+                plain_text,
+                parsed_file,
+                menu,
+                browser,
+                fetch_title,
+                clipboard,
+                bookmark,
+            )?,
+            OPTIONS_REMOVE => remove(
+                plain_text,
+                parsed_file,
+                menu,
+                browser,
+                fetch_title,
+                clipboard,
+                bookmark,
+            )?,
             OPTIONS_CANCEL => {
-                show_list(plain_text, parsed_file, menu, browser)?;
+                show_list(
+                    plain_text,
+                    parsed_file,
+                    menu,
+                    browser,
+                    fetch_title,
+                    clipboard,
+                )?;
             }
             _ => (),
         };
     } else if file_line.contains(&add_bookmark_option_string) {
-        add(plain_text, parsed_file, menu, browser)?;
+        add(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
     };
 
     Ok(())
@@ -99,32 +278,120 @@ fn add(
     parsed_file: &mut ParsedFile,
     menu: Menu,
     browser: String,
+    fetch_title: bool,
+    clipboard: Clipboard,
 ) -> Result<(), String> {
-    let title = menu.choose(None, None, TITLE)?;
-    if title.is_empty() {
-        show_list(plain_text, parsed_file, menu, browser)?;
+    let clipboard_url = clipboard.url();
+    let url = menu.choose(clipboard_url.as_deref(), None, URL)?.text();
+    let Some(url) = url.filter(|url| !url.is_empty()) else {
+        show_list(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
         return Ok(());
+    };
+
+    let mut on_duplicate = OnDuplicate::KeepBoth;
+    if let Some(existing) = parsed_file.find_duplicate(&url) {
+        let prompt = format!("{} is already bookmarked", existing.url());
+        on_duplicate = match menu
+            .choose(Some(DUPLICATE_OPTIONS), None, &prompt)?
+            .text()
+            .as_deref()
+        {
+            Some(DUPLICATE_KEEP_BOTH) => OnDuplicate::KeepBoth,
+            Some(DUPLICATE_MERGE) => OnDuplicate::Merge,
+            _ => {
+                show_list(
+                    plain_text,
+                    parsed_file,
+                    menu,
+                    browser,
+                    fetch_title,
+                    clipboard,
+                )?;
+                return Ok(());
+            }
+        };
     }
 
+    // The fetched title is offered as the only item, so it can be picked or typed over
+    let page_title = match fetch_title {
+        true => title::fetch(&url).ok(),
+        false => None,
+    };
+    let title = menu.choose(page_title.as_deref(), None, TITLE)?.text();
+    let Some(title) = title.filter(|title| !title.is_empty()) else {
+        show_list(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
+        return Ok(());
+    };
+
     plain_text.update_categories(parsed_file);
     let categories = Some(plain_text.categories());
-    let category = menu.choose(categories, None, CATEGORY)?;
-    if category.is_empty() {
-        show_list(plain_text, parsed_file, menu, browser)?;
+    let category = menu.choose(categories, None, CATEGORY)?.text();
+    let Some(category) = category.filter(|category| !category.is_empty()) else {
+        show_list(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
         return Ok(());
-    }
+    };
 
-    let url = menu.choose(None, None, URL)?;
-    if url.is_empty() {
-        show_list(plain_text, parsed_file, menu, browser)?;
+    // Tags and a description are optional, so only cancelling discards the bookmark
+    let tags = parsed_file.tags().join("\n");
+    let Some(tags) = menu.choose(Some(&tags), None, TAGS)?.text() else {
+        show_list(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
         return Ok(());
-    }
+    };
 
-    let new_bookmark = Bookmark::new(title, category, url);
+    let Some(description) = menu.choose(None, None, DESCRIPTION)?.text() else {
+        show_list(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
+        return Ok(());
+    };
+
+    let new_bookmark = Bookmark::new(title, category, url)
+        .with_tags(Bookmark::parse_tags(&tags))
+        .with_description(&description);
 
-    parsed_file.add_bookmark(plain_text, new_bookmark);
+    parsed_file.add_bookmark_or_resolve(plain_text, new_bookmark, &on_duplicate);
 
-    show_list(plain_text, parsed_file, menu, browser)
+    show_list(
+        plain_text,
+        parsed_file,
+        menu,
+        browser,
+        fetch_title,
+        clipboard,
+    )
 }
 
 fn modify(
@@ -132,12 +399,24 @@ fn modify(
     parsed_file: &mut ParsedFile,
     menu: Menu,
     browser: String,
+    fetch_title: bool,
+    clipboard: Clipboard,
     bookmark: Bookmark,
 ) -> Result<(), String> {
     let mut title = bookmark.title().to_string();
-    title = menu.choose(Some(&title), None, TITLE)?;
+    title = menu
+        .choose(Some(&title), None, TITLE)?
+        .text()
+        .unwrap_or_default();
     if title.is_empty() {
-        show_list(plain_text, parsed_file, menu, browser)?;
+        show_list(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
         return Ok(());
     }
 
@@ -149,10 +428,19 @@ fn modify(
         .categories()
         .replace(&format!("{}\n", old_category), "");
 
-    let mut new_category =
-        menu.choose(Some(&categories), Some(&old_category_w_indicator), CATEGORY)?;
+    let mut new_category = menu
+        .choose(Some(&categories), Some(&old_category_w_indicator), CATEGORY)?
+        .text()
+        .unwrap_or_default();
     if new_category.is_empty() {
-        show_list(plain_text, parsed_file, menu, browser)?;
+        show_list(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
         return Ok(());
     }
     if new_category == old_category_w_indicator {
@@ -160,17 +448,63 @@ fn modify(
     }
 
     let mut url = bookmark.url().to_string();
-    url = menu.choose(Some(&url), None, URL)?;
+    url = menu
+        .choose(Some(&url), None, URL)?
+        .text()
+        .unwrap_or_default();
     if url.is_empty() {
-        show_list(plain_text, parsed_file, menu, browser)?;
+        show_list(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
         return Ok(());
     }
 
-    let new_bookmark = Bookmark::new(title, new_category, url);
+    let tags = bookmark.joined_tags();
+    let Some(tags) = menu.choose(Some(&tags), None, TAGS)?.text() else {
+        show_list(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
+        return Ok(());
+    };
+
+    let description = bookmark.description().to_string();
+    let Some(description) = menu.choose(Some(&description), None, DESCRIPTION)?.text() else {
+        show_list(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
+        return Ok(());
+    };
+
+    let new_bookmark = Bookmark::new(title, new_category, url)
+        .with_tags(Bookmark::parse_tags(&tags))
+        .with_description(&description)
+        .with_history_of(&bookmark);
 
     parsed_file.modify_bookmark(plain_text, new_bookmark, &bookmark);
 
-    show_list(plain_text, parsed_file, menu, browser)
+    show_list(
+        plain_text,
+        parsed_file,
+        menu,
+        browser,
+        fetch_title,
+        clipboard,
+    )
 }
 
 fn remove(
@@ -178,16 +512,76 @@ fn remove(
     parsed_file: &mut ParsedFile,
     menu: Menu,
     browser: String,
+    fetch_title: bool,
+    clipboard: Clipboard,
     bookmark: Bookmark,
 ) -> Result<(), String> {
-    let prompt = format!("Remove {}? (yes/no)", bookmark.title().trim());
-    let answer = menu.choose(None, None, &prompt)?;
-    if answer.to_lowercase() != "yes" {
-        show_list(plain_text, parsed_file, menu, browser)?;
+    let prompt = format!("Remove {}?", bookmark.title().trim());
+    let answer = menu.choose(Some(REMOVE_OPTIONS), None, &prompt)?.text();
+    if !answer.is_some_and(|answer| answer.eq_ignore_ascii_case(REMOVE_YES)) {
+        show_list(
+            plain_text,
+            parsed_file,
+            menu,
+            browser,
+            fetch_title,
+            clipboard,
+        )?;
         return Ok(());
     }
 
-    parsed_file.remove_bookmark(plain_text, bookmark.url());
+    parsed_file.remove_bookmark(plain_text, &bookmark);
+
+    show_list(
+        plain_text,
+        parsed_file,
+        menu,
+        browser,
+        fetch_title,
+        clipboard,
+    )
+}
 
-    show_list(plain_text, parsed_file, menu, browser)
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_modify_cancelled() {
+        for cancelled_prompt in [TAGS, DESCRIPTION] {
+            let bookmark = Bookmark::new(
+                "title".to_string(),
+                "category".to_string(),
+                "https://example.com".to_string(),
+            )
+            .with_tags(vec!["rust".to_string()])
+            .with_description("a description");
+            let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+            let mut parsed_file = ParsedFile::new(&bookmark.to_line(0, 0));
+
+            // Every prompt takes the first item, except the cancelled one and the list
+            let template = format!(
+                "sh -c 'case \"$1\" in \"{}\"|bookmarks) exit 1;; *) head -n 1;; esac' sh {{prompt}}",
+                cancelled_prompt
+            );
+            let menu = Menu::new(template, "1".to_string(), true).unwrap();
+            modify(
+                &mut plain_text,
+                &mut parsed_file,
+                menu,
+                "true".to_string(),
+                false,
+                Clipboard::Disabled,
+                bookmark.clone(),
+            )
+            .unwrap();
+
+            let kept = parsed_file.all_bookmarks().next().unwrap();
+            assert_eq!(kept.tags(), bookmark.tags());
+            assert_eq!(kept.description(), "a description");
+            assert!(!plain_text.edited());
+        }
+    }
 }
@@ -1,30 +1,124 @@
-use std::io::Write;
+use std::collections::HashMap;
+use std::io::{ErrorKind, Write};
 use std::process::{Command, Stdio};
 
+use crate::tui;
+
+pub const PROMPT_PLACEHOLDER: &str = "{prompt}";
+pub const ROWS_PLACEHOLDER: &str = "{rows}";
+pub const IGNORE_CASE_PLACEHOLDER: &str = "{ignore_case:";
+
+// Previews follow the line after a tab, with escapes that printf expands
+const PREVIEW_DELIMITER: char = '\t';
+const PREVIEW_COMMAND: &str = "printf '%b' {2}";
+const PREVIEW_WINDOW: &str = "right:50%:wrap";
+// Launchers exit with 1 when cancelled, fzf and sk with 130
+const CANCELLED_EXIT_CODES: [i32; 2] = [1, 130];
+
+// Launchers that fail with another exit code are reported as an error with their stderr
+#[derive(Debug, PartialEq)]
+pub enum Choice {
+    Selected(String),
+    Typed(String),
+    Cancelled,
+}
+
+impl Choice {
+    pub fn text(self) -> Option<String> {
+        match self {
+            Self::Selected(text) | Self::Typed(text) => Some(text),
+            Self::Cancelled => None,
+        }
+    }
+}
+
 pub enum Menu {
-    Bemenu { rows: String },
-    Dmenu { rows: String },
-    Rofi { rows: String },
-    Fzf,
+    Bemenu {
+        rows: String,
+        ignore_case: bool,
+    },
+    Dmenu {
+        rows: String,
+        ignore_case: bool,
+    },
+    Rofi {
+        rows: String,
+        ignore_case: bool,
+    },
+    Fzf {
+        ignore_case: bool,
+    },
+    Sk {
+        ignore_case: bool,
+    },
+    Wofi {
+        rows: String,
+        ignore_case: bool,
+    },
+    // fuzzel and tofi always match case insensitively
+    Fuzzel {
+        rows: String,
+    },
+    Tofi {
+        rows: String,
+    },
+    Tui {
+        rows: String,
+        ignore_case: bool,
+    },
+    Custom {
+        template: Vec<String>,
+        rows: String,
+        ignore_case: bool,
+    },
 }
 
 impl Menu {
-    pub fn new(menu_program: String, rows: String) -> Result<Self, String> {
+    pub fn new(menu_program: String, rows: String, ignore_case: bool) -> Result<Self, String> {
         match menu_program.as_str() {
-            "bemenu" => Ok(Self::Bemenu { rows }),
-            "dmenu" => Ok(Self::Dmenu { rows }),
-            "rofi" => Ok(Self::Rofi { rows }),
-            "fzf" => Ok(Self::Fzf),
-            _ => Err(format!("Unsupported menu program: {}", menu_program)),
+            "bemenu" => Ok(Self::Bemenu { rows, ignore_case }),
+            "dmenu" => Ok(Self::Dmenu { rows, ignore_case }),
+            "rofi" => Ok(Self::Rofi { rows, ignore_case }),
+            "fzf" => Ok(Self::Fzf { ignore_case }),
+            "sk" => Ok(Self::Sk { ignore_case }),
+            "wofi" => Ok(Self::Wofi { rows, ignore_case }),
+            "fuzzel" => Ok(Self::Fuzzel { rows }),
+            "tofi" => Ok(Self::Tofi { rows }),
+            "tui" => Ok(Self::Tui { rows, ignore_case }),
+            _ => {
+                let template = Self::split_arguments(&menu_program)?;
+                if template.is_empty() {
+                    return Err("Missing menu program.".to_string());
+                }
+                Ok(Self::Custom {
+                    template,
+                    rows,
+                    ignore_case,
+                })
+            }
         }
     }
 
+    pub fn shows_previews(&self) -> bool {
+        matches!(self, Self::Fzf { .. } | Self::Sk { .. })
+    }
+
     pub fn choose(
         &self,
         menu_items: Option<&str>,
         default: Option<&str>,
         prompt: &str,
-    ) -> Result<String, String> {
+    ) -> Result<Choice, String> {
+        self.choose_with_previews(menu_items, default, prompt, &HashMap::new())
+    }
+
+    pub fn choose_with_previews(
+        &self,
+        menu_items: Option<&str>,
+        default: Option<&str>,
+        prompt: &str,
+        previews: &HashMap<String, String>,
+    ) -> Result<Choice, String> {
         let menu_items = match (menu_items, default) {
             (Some(items), Some(default)) => {
                 let mut items = items.to_string();
@@ -37,58 +131,301 @@ impl Menu {
         };
 
         let output = match self {
-            Self::Bemenu { rows } => {
-                self.run_command("bemenu", &["-i", "-l", rows, "-p", prompt], menu_items)?
-            }
-            Self::Dmenu { rows } => {
-                self.run_command("dmenu", &["-i", "-l", rows, "-p", prompt], menu_items)?
-            }
-            Self::Rofi { rows } => self.run_command(
-                "rofi",
-                &["-dmenu", "-i", "-l", rows, "-p", prompt],
-                menu_items,
+            Self::Bemenu { rows, ignore_case } => self.run_template(
+                &[
+                    "bemenu",
+                    "{ignore_case:-i}",
+                    "-l",
+                    "{rows}",
+                    "-p",
+                    "{prompt}",
+                ],
+                rows,
+                *ignore_case,
+                prompt,
+                menu_items.as_deref(),
+            )?,
+            Self::Dmenu { rows, ignore_case } => self.run_template(
+                &[
+                    "dmenu",
+                    "{ignore_case:-i}",
+                    "-l",
+                    "{rows}",
+                    "-p",
+                    "{prompt}",
+                ],
+                rows,
+                *ignore_case,
+                prompt,
+                menu_items.as_deref(),
             )?,
-            Self::Fzf => {
+            Self::Rofi { rows, ignore_case } => self.run_template(
+                &[
+                    "rofi",
+                    "-dmenu",
+                    "{ignore_case:-i}",
+                    "-l",
+                    "{rows}",
+                    "-p",
+                    "{prompt}",
+                ],
+                rows,
+                *ignore_case,
+                prompt,
+                menu_items.as_deref(),
+            )?,
+            Self::Fzf { ignore_case } | Self::Sk { ignore_case } => {
+                let (program, case) = match (self, ignore_case) {
+                    (Self::Fzf { .. }, true) => ("fzf", "-i"),
+                    (Self::Fzf { .. }, false) => ("fzf", "+i"),
+                    (_, true) => ("sk", "--case=ignore"),
+                    (_, false) => ("sk", "--case=respect"),
+                };
                 let prompt = format!("{}> ", prompt);
-                let menu_items = menu_items.unwrap_or("".to_string());
-                self.run_command(
-                    "fzf",
-                    &["-i", "--print-query", "--prompt", &prompt],
-                    Some(menu_items),
-                )?
+                let delimiter = PREVIEW_DELIMITER.to_string();
+                let mut args = vec![case, "--print-query", "--prompt", &prompt];
+                let mut menu_items = menu_items.unwrap_or("".to_string());
+                if !previews.is_empty() {
+                    args.extend([
+                        "--delimiter",
+                        &delimiter,
+                        "--with-nth",
+                        "1",
+                        "--preview",
+                        PREVIEW_COMMAND,
+                        "--preview-window",
+                        PREVIEW_WINDOW,
+                    ]);
+                    menu_items = Self::with_previews(&menu_items, previews);
+                }
+                let output = self.run_command(program, &args, Some(&menu_items))?;
+                return Ok(
+                    output.map_or(Choice::Cancelled, |output| Self::parse_print_query(&output))
+                );
             }
+            Self::Wofi { rows, ignore_case } => self.run_template(
+                &[
+                    "wofi",
+                    "--dmenu",
+                    "{ignore_case:--insensitive}",
+                    "--lines",
+                    "{rows}",
+                    "--prompt",
+                    "{prompt}",
+                ],
+                rows,
+                *ignore_case,
+                prompt,
+                menu_items.as_deref(),
+            )?,
+            Self::Fuzzel { rows } => self.run_template(
+                &[
+                    "fuzzel",
+                    "--dmenu",
+                    "--lines",
+                    "{rows}",
+                    "--prompt",
+                    "{prompt}> ",
+                ],
+                rows,
+                true,
+                prompt,
+                menu_items.as_deref(),
+            )?,
+            // Without '--require-match false' new titles and urls could not be typed
+            Self::Tofi { rows } => self.run_template(
+                &[
+                    "tofi",
+                    "--require-match",
+                    "false",
+                    "--num-results",
+                    "{rows}",
+                    "--prompt-text",
+                    "{prompt}: ",
+                ],
+                rows,
+                true,
+                prompt,
+                menu_items.as_deref(),
+            )?,
+            Self::Tui { rows, ignore_case } => {
+                return tui::choose(
+                    menu_items.as_deref(),
+                    prompt,
+                    rows.parse().unwrap_or(1),
+                    *ignore_case,
+                )
+            }
+            Self::Custom {
+                template,
+                rows,
+                ignore_case,
+            } => self.run_template(template, rows, *ignore_case, prompt, menu_items.as_deref())?,
+        };
+
+        Ok(Self::classify(output, menu_items.as_deref()))
+    }
+
+    fn classify(output: Option<String>, menu_items: Option<&str>) -> Choice {
+        let Some(output) = output else {
+            return Choice::Cancelled;
         };
+        let output = output.trim().to_string();
+        let is_item = !output.is_empty()
+            && menu_items.is_some_and(|items| items.lines().any(|item| item.trim() == output));
+        match is_item {
+            true => Choice::Selected(output),
+            false => Choice::Typed(output),
+        }
+    }
+
+    // '--print-query' puts the query on the first line and the selected item, if any, on the second
+    fn parse_print_query(output: &str) -> Choice {
+        let output = output.strip_suffix('\n').unwrap_or(output);
+        match output.split_once('\n') {
+            Some((_, selected)) => {
+                let selected = selected.lines().next().unwrap_or("");
+                let selected = selected.split(PREVIEW_DELIMITER).next().unwrap_or("");
+                Choice::Selected(selected.trim().to_string())
+            }
+            None => Choice::Typed(output.trim().to_string()),
+        }
+    }
 
-        Ok(output)
+    fn with_previews(menu_items: &str, previews: &HashMap<String, String>) -> String {
+        menu_items
+            .lines()
+            .map(|line| match previews.get(line) {
+                Some(preview) => {
+                    let preview = preview
+                        .replace('\\', "\\\\")
+                        .replace('\n', "\\n")
+                        .replace(PREVIEW_DELIMITER, "\\t");
+                    format!("{}{}{}\n", line, PREVIEW_DELIMITER, preview)
+                }
+                None => format!("{}\n", line),
+            })
+            .collect()
+    }
+
+    fn run_template<S: AsRef<str>>(
+        &self,
+        template: &[S],
+        rows: &str,
+        ignore_case: bool,
+        prompt: &str,
+        input: Option<&str>,
+    ) -> Result<Option<String>, String> {
+        let command = Self::fill_template(template, rows, ignore_case, prompt);
+        let (cmd, args) = command
+            .split_first()
+            .ok_or_else(|| "Missing menu program.".to_string())?;
+        let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+        self.run_command(cmd, &args, input)
+    }
+
+    fn fill_template<S: AsRef<str>>(
+        template: &[S],
+        rows: &str,
+        ignore_case: bool,
+        prompt: &str,
+    ) -> Vec<String> {
+        let mut command = Vec::with_capacity(template.len());
+        for arg in template {
+            let mut arg = arg.as_ref().to_string();
+            let had_placeholder = arg.contains('{');
+            while let Some(start) = arg.find(IGNORE_CASE_PLACEHOLDER) {
+                let Some(end) = arg[start..].find('}').map(|end| start + end) else {
+                    break;
+                };
+                let flag = match ignore_case {
+                    true => arg[start + IGNORE_CASE_PLACEHOLDER.len()..end].to_string(),
+                    false => String::new(),
+                };
+                arg.replace_range(start..=end, &flag);
+            }
+            let arg = arg
+                .replace(ROWS_PLACEHOLDER, rows)
+                .replace(PROMPT_PLACEHOLDER, prompt);
+            // Placeholders that expand to nothing would otherwise be passed as empty arguments
+            if !(had_placeholder && arg.is_empty()) {
+                command.push(arg);
+            }
+        }
+        command
+    }
+
+    pub fn split_arguments(template: &str) -> Result<Vec<String>, String> {
+        let mut args = Vec::new();
+        let mut arg: Option<String> = None;
+        let mut quote = None;
+        for c in template.chars() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), c) => arg.get_or_insert_with(String::new).push(c),
+                (None, '"' | '\'') => {
+                    quote = Some(c);
+                    arg.get_or_insert_with(String::new);
+                }
+                (None, c) if c.is_whitespace() => args.extend(arg.take()),
+                (None, c) => arg.get_or_insert_with(String::new).push(c),
+            }
+        }
+        if quote.is_some() {
+            return Err(format!("Unterminated quote in: {}", template));
+        }
+        args.extend(arg);
+        Ok(args)
     }
 
     fn run_command(
         &self,
         cmd: &str,
         args: &[&str],
-        input: Option<String>,
-    ) -> Result<String, String> {
+        input: Option<&str>,
+    ) -> Result<Option<String>, String> {
         let mut child = Command::new(cmd)
             .args(args)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
             .spawn()
             .map_err(|_| format!("Failed to execute command: {}", cmd))?;
 
         if let Some(input) = input {
             if let Some(stdin) = child.stdin.as_mut() {
-                stdin
-                    .write_all(input.as_bytes())
-                    .map_err(|_| "Failed to write to stdin".to_string())?;
+                // Launchers may exit before reading every item
+                match stdin.write_all(input.as_bytes()) {
+                    Err(error) if error.kind() != ErrorKind::BrokenPipe => {
+                        return Err("Failed to write to stdin".to_string())
+                    }
+                    _ => (),
+                }
             }
         }
 
         let output = child
             .wait_with_output()
             .map_err(|_| "Failed to wait on child".to_string())?;
-        String::from_utf8(output.stdout)
-            .map_err(|_| "Invalid UTF-8 sequence".to_string())
-            .map(|v| v.trim().to_string())
+        let stdout =
+            String::from_utf8(output.stdout).map_err(|_| "Invalid UTF-8 sequence".to_string())?;
+        if output.status.success() {
+            return Ok(Some(stdout));
+        }
+
+        // rofi and wofi often print warnings, so stderr alone does not mean the launcher failed
+        match output.status.code() {
+            // fzf and sk exit with 1 but still print the query when nothing matches it
+            Some(1) if !stdout.is_empty() => Ok(Some(stdout)),
+            Some(code) if !CANCELLED_EXIT_CODES.contains(&code) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Err(match stderr.trim() {
+                    "" => format!("{} failed with exit code {}", cmd, code),
+                    stderr => format!("{} failed: {}", cmd, stderr),
+                })
+            }
+            _ => Ok(None),
+        }
     }
 }
 
@@ -98,19 +435,139 @@ mod tests {
 
     #[test]
     fn test_menu_new() {
-        let menu = Menu::new("bemenu".to_string(), "10".to_string());
-        assert!(menu.is_ok());
+        let menu = Menu::new("bemenu".to_string(), "10".to_string(), true);
+        assert!(matches!(menu, Ok(Menu::Bemenu { .. })));
+        let menu = Menu::new("sk".to_string(), "10".to_string(), true);
+        assert!(matches!(menu, Ok(Menu::Sk { .. })));
+        let menu = Menu::new("tui".to_string(), "10".to_string(), true);
+        assert!(matches!(menu, Ok(Menu::Tui { .. })));
+        let menu = Menu::new("wofi".to_string(), "10".to_string(), true);
+        assert!(matches!(menu, Ok(Menu::Wofi { .. })));
+        let menu = Menu::new("fuzzel".to_string(), "10".to_string(), true);
+        assert!(matches!(menu, Ok(Menu::Fuzzel { .. })));
+        let menu = Menu::new("tofi".to_string(), "10".to_string(), true);
+        assert!(matches!(menu, Ok(Menu::Tofi { .. })));
 
-        let menu = Menu::new("unsupported".to_string(), "10".to_string());
-        assert!(menu.is_err());
+        // Test that other programs are used as a command template
+        let menu = Menu::new(
+            "launcher --dmenu -p '{prompt}: ' {ignore_case:-i}".to_string(),
+            "10".to_string(),
+            true,
+        );
+        match menu {
+            Ok(Menu::Custom { template, .. }) => {
+                assert_eq!(
+                    template,
+                    [
+                        "launcher",
+                        "--dmenu",
+                        "-p",
+                        "{prompt}: ",
+                        "{ignore_case:-i}"
+                    ]
+                )
+            }
+            _ => panic!("expected a custom menu"),
+        }
+
+        assert!(Menu::new(" ".to_string(), "10".to_string(), true).is_err());
+        assert!(Menu::new("launcher -p '{prompt}".to_string(), "10".to_string(), true).is_err());
+    }
+
+    #[test]
+    fn test_menu_fill_template() {
+        let template = [
+            "launcher",
+            "--lines={rows}",
+            "{ignore_case:--insensitive}",
+            "--prompt",
+            "{prompt}: ",
+        ];
+        assert_eq!(
+            Menu::fill_template(&template, "10", true, "a title"),
+            [
+                "launcher",
+                "--lines=10",
+                "--insensitive",
+                "--prompt",
+                "a title: "
+            ]
+        );
+        assert_eq!(
+            Menu::fill_template(&template, "10", false, ""),
+            ["launcher", "--lines=10", "--prompt", ": "]
+        );
+
+        // Test that a custom command receives the items and returns the choice
+        let menu = Menu::new("head -n {rows}".to_string(), "1".to_string(), true).unwrap();
+        let result = menu.choose(Some("first\nsecond\n"), Some("second"), "prompt");
+        assert_eq!(result, Ok(Choice::Selected("second".to_string())));
     }
 
     #[test]
     fn test_menu_choose() {
-        let menu = Menu::new("bemenu".to_string(), "10".to_string()).unwrap();
+        let menu = Menu::new("bemenu".to_string(), "10".to_string(), true).unwrap();
         let result = menu.choose(Some("pass\nfail"), Some("pass"), "Choose an item");
         if let Ok(result) = result {
-            assert_eq!(result, "pass");
+            assert_eq!(result, Choice::Selected("pass".to_string()));
         }
     }
+
+    #[test]
+    fn test_menu_with_previews() {
+        let previews = HashMap::from([(
+            "{T}{a} {C}{b} {U}{c}".to_string(),
+            "Title: a\\b\n\tc\n".to_string(),
+        )]);
+        assert_eq!(
+            Menu::with_previews("{T}{a} {C}{b} {U}{c}\n-| Add Bookmark |-\n", &previews),
+            "{T}{a} {C}{b} {U}{c}\tTitle: a\\\\b\\n\\tc\\n\n-| Add Bookmark |-\n"
+        );
+    }
+
+    #[test]
+    fn test_menu_choice() {
+        let choose = |template: &str| {
+            let menu = Menu::new(template.to_string(), "1".to_string(), true).unwrap();
+            menu.choose(Some("first\nsecond"), None, "prompt")
+        };
+        assert_eq!(
+            choose("sh -c 'cat >/dev/null; echo new'"),
+            Ok(Choice::Typed("new".to_string()))
+        );
+        assert_eq!(
+            choose("sh -c 'cat >/dev/null; echo'"),
+            Ok(Choice::Typed(String::new()))
+        );
+        assert_eq!(choose("sh -c 'exit 1'"), Ok(Choice::Cancelled));
+
+        // Test that warnings on stderr do not turn a cancelled menu into an error
+        assert_eq!(
+            choose("sh -c 'echo Gtk-WARNING: theme not found >&2; exit 1'"),
+            Ok(Choice::Cancelled)
+        );
+
+        // Test that a failing launcher is reported with its error output
+        assert_eq!(
+            choose("sh -c 'echo cannot open display >&2; exit 2'"),
+            Err("sh failed: cannot open display".to_string())
+        );
+        assert_eq!(
+            choose("sh -c 'exit 3'"),
+            Err("sh failed with exit code 3".to_string())
+        );
+
+        assert_eq!(
+            Menu::parse_print_query("ru\nrust\tTitle: rust\n"),
+            Choice::Selected("rust".to_string())
+        );
+        assert_eq!(
+            Menu::parse_print_query("\nrust\n"),
+            Choice::Selected("rust".to_string())
+        );
+        assert_eq!(
+            Menu::parse_print_query("new\n"),
+            Choice::Typed("new".to_string())
+        );
+    }
 }
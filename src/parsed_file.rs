@@ -1,11 +1,41 @@
 use std::collections::HashMap;
 
 use crate::bookmark::Bookmark;
+use crate::journal::Edit;
 use crate::plain_text::PlainText;
-use crate::{ADD_BOOKMARK, CATEGORY_MAX_LENGTH, SEPARATOR_LINE_SYMBOL, TITLE_MAX_LENGTH};
+use crate::url;
+use crate::{
+    ADD_BOOKMARK, CATEGORY_MAX_LENGTH, FILTER_BY_TAG, REDO_LAST_CHANGE, SEPARATOR_LINE_SYMBOL,
+    TITLE_MAX_LENGTH, UNDO_LAST_CHANGE,
+};
+
+pub const SUPPORTED_ON_DUPLICATE: [&str; 3] = ["keep", "merge", "skip"];
+
+pub enum OnDuplicate {
+    KeepBoth,
+    Merge,
+    Skip,
+}
+
+impl OnDuplicate {
+    pub fn new(on_duplicate: &str) -> Result<Self, String> {
+        match on_duplicate {
+            "keep" => Ok(Self::KeepBoth),
+            "merge" => Ok(Self::Merge),
+            "skip" => Ok(Self::Skip),
+            _ => Err(format!(
+                "Unsupported duplicate handling: {}. Supported are '{}'.",
+                on_duplicate,
+                SUPPORTED_ON_DUPLICATE.join("', '")
+            )),
+        }
+    }
+}
 
 pub struct ParsedFile {
     pub bookmarks: HashMap<String, Bookmark>,
+    duplicates: Vec<Bookmark>,
+    normalized_urls: HashMap<String, Vec<String>>,
     titles_char_count: Vec<usize>,
     pub longest_title: usize,
     pub invalid_lines: HashMap<usize, String>,
@@ -13,12 +43,16 @@ pub struct ParsedFile {
     category_count: HashMap<String, usize>,
     categories_char_count: Vec<usize>,
     pub longest_category: usize,
+    tags: Vec<String>,
+    tag_count: HashMap<String, usize>,
 }
 
 impl ParsedFile {
     pub fn new(plain_text_bookmarks: &str) -> Self {
         let mut parsed_file = ParsedFile {
             bookmarks: HashMap::new(),
+            duplicates: Vec::new(),
+            normalized_urls: HashMap::new(),
             titles_char_count: vec![0; TITLE_MAX_LENGTH + 1],
             invalid_lines: HashMap::new(),
             categories: Vec::new(),
@@ -26,6 +60,8 @@ impl ParsedFile {
             categories_char_count: vec![0; CATEGORY_MAX_LENGTH + 1],
             longest_title: 0,
             longest_category: 0,
+            tags: Vec::new(),
+            tag_count: HashMap::new(),
         };
 
         let lines = plain_text_bookmarks.lines();
@@ -38,9 +74,8 @@ impl ParsedFile {
                 Some(bookmark) => {
                     parsed_file.add_titles_char_count(bookmark.title());
                     parsed_file.add_category(bookmark.category().to_string());
-                    parsed_file
-                        .bookmarks
-                        .insert(bookmark.url().to_string(), bookmark);
+                    parsed_file.add_tags(bookmark.tags());
+                    parsed_file.store(bookmark);
                 }
                 None => {
                     parsed_file.invalid_lines.insert(i, line.to_string());
@@ -49,7 +84,8 @@ impl ParsedFile {
         }
         parsed_file
             .categories
-            .sort_by(|a, b| PlainText::alphabetic_sort(a, b));
+            .sort_by(|a, b| Self::name_order(a, b));
+        parsed_file.tags.sort_by(|a, b| Self::name_order(a, b));
 
         parsed_file
     }
@@ -58,33 +94,132 @@ impl ParsedFile {
         &self.categories
     }
 
-    pub fn add_bookmark(&mut self, plain_text: &mut PlainText, new_bookmark: Bookmark) {
+    pub fn tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+
+    pub fn duplicates(&self) -> &[Bookmark] {
+        &self.duplicates
+    }
+
+    pub fn duplicate_urls(&self) -> Vec<&str> {
+        let mut urls: Vec<&str> = self.duplicates.iter().map(|b| b.url()).collect();
+        urls.sort();
+        urls.dedup();
+        urls
+    }
+
+    pub fn all_bookmarks(&self) -> impl Iterator<Item = &Bookmark> {
+        self.bookmarks.values().chain(self.duplicates.iter())
+    }
+
+    pub fn find_duplicate(&self, url: &str) -> Option<&Bookmark> {
+        if let Some(bookmark) = self.bookmarks.get(url) {
+            return Some(bookmark);
+        }
+        self.normalized_urls
+            .get(&url::normalize(url))?
+            .first()
+            .and_then(|url| self.bookmarks.get(url))
+    }
+
+    // Menu lines leave out the history, so the stored copy is looked up through the url
+    pub fn find_menu_bookmark(&self, menu_bookmark: &Bookmark) -> Option<&Bookmark> {
+        let menu_line = menu_bookmark.to_menu_line(0, 0);
+        let copies = self.bookmarks.get(menu_bookmark.url()).into_iter().chain(
+            self.duplicates
+                .iter()
+                .filter(|duplicate| duplicate.url() == menu_bookmark.url()),
+        );
+        copies
+            .clone()
+            .find(|copy| copy.to_menu_line(0, 0) == menu_line)
+            .or_else(|| copies.into_iter().next())
+    }
+
+    pub fn duplicate_groups(&self) -> Vec<(String, Vec<&Bookmark>)> {
+        let mut groups: HashMap<String, Vec<&Bookmark>> = HashMap::new();
+        for bookmark in self.all_bookmarks() {
+            groups
+                .entry(url::normalize(bookmark.url()))
+                .or_default()
+                .push(bookmark);
+        }
+
+        let mut groups: Vec<(String, Vec<&Bookmark>)> = groups
+            .into_iter()
+            .filter(|(_, bookmarks)| bookmarks.len() > 1)
+            .collect();
+        for (_, bookmarks) in groups.iter_mut() {
+            // Oldest first, bookmarks without an added time last
+            bookmarks.sort_by(|a, b| {
+                (a.added().is_none(), a.added(), a.url(), a.title()).cmp(&(
+                    b.added().is_none(),
+                    b.added(),
+                    b.url(),
+                    b.title(),
+                ))
+            });
+        }
+        groups.sort_by(|a, b| a.0.cmp(&b.0));
+        groups
+    }
+
+    pub fn add_bookmark(&mut self, plain_text: &mut PlainText, mut new_bookmark: Bookmark) {
+        if new_bookmark.added().is_none() {
+            new_bookmark = new_bookmark.with_added(Some(Bookmark::timestamp_now()));
+        }
         if self.add_category(new_bookmark.category().to_string()) {
             plain_text.increment_categories_version();
         };
         self.add_titles_char_count(new_bookmark.title());
-        self.bookmarks
-            .insert(new_bookmark.url().to_string(), new_bookmark);
+        self.add_tags(new_bookmark.tags());
+        plain_text.journal_mut().record(None, Some(&new_bookmark));
+        self.store(new_bookmark);
         plain_text.increment_bookmarks_version();
         plain_text.set_edited_true();
     }
 
+    pub fn add_bookmark_or_resolve(
+        &mut self,
+        plain_text: &mut PlainText,
+        new_bookmark: Bookmark,
+        on_duplicate: &OnDuplicate,
+    ) -> bool {
+        let existing = match self.find_duplicate(new_bookmark.url()) {
+            Some(existing) => existing.clone(),
+            None => {
+                self.add_bookmark(plain_text, new_bookmark);
+                return true;
+            }
+        };
+        match on_duplicate {
+            OnDuplicate::KeepBoth => self.add_bookmark(plain_text, new_bookmark),
+            OnDuplicate::Merge => {
+                self.modify_bookmark(plain_text, existing.merged_with(&new_bookmark), &existing)
+            }
+            OnDuplicate::Skip => return false,
+        }
+        true
+    }
+
     pub fn modify_bookmark(
         &mut self,
         plain_text: &mut PlainText,
         new_bookmark: Bookmark,
         old_bookmark: &Bookmark,
     ) {
-        if old_bookmark == &new_bookmark {
+        if old_bookmark == &new_bookmark || self.unstore(old_bookmark).is_none() {
             return;
         }
+        plain_text
+            .journal_mut()
+            .record(Some(old_bookmark), Some(&new_bookmark));
 
         let old_title = old_bookmark.title();
         let old_category = old_bookmark.category();
-        let old_url = old_bookmark.url();
         let new_title = new_bookmark.title();
         let new_category = new_bookmark.category();
-        let new_url = new_bookmark.url();
 
         if old_title != new_title {
             self.remove_titles_char_count(old_title);
@@ -97,28 +232,236 @@ impl ParsedFile {
                 plain_text.increment_categories_version();
             }
         }
-        if old_url != new_url {
-            self.bookmarks.remove(old_url);
-            self.bookmarks.insert(new_url.to_string(), new_bookmark);
-        } else {
-            self.bookmarks.insert(old_url.to_string(), new_bookmark);
+        if old_bookmark.tags() != new_bookmark.tags() {
+            self.remove_tags(old_bookmark.tags());
+            self.add_tags(new_bookmark.tags());
         }
+        self.store(new_bookmark);
         plain_text.increment_bookmarks_version();
         plain_text.set_edited_true();
     }
 
-    pub fn remove_bookmark(&mut self, plain_text: &mut PlainText, url: &str) {
-        if let Some(bookmark) = self.bookmarks.remove(url) {
+    pub fn remove_bookmark(&mut self, plain_text: &mut PlainText, bookmark: &Bookmark) {
+        if let Some(bookmark) = self.unstore(bookmark) {
+            plain_text.journal_mut().record(Some(&bookmark), None);
             let category = bookmark.category();
             if self.remove_category(category) {
                 plain_text.increment_categories_version();
             }
             self.remove_titles_char_count(bookmark.title());
+            self.remove_tags(bookmark.tags());
             plain_text.increment_bookmarks_version();
             plain_text.set_edited_true();
         }
     }
 
+    pub fn visit_bookmark(&mut self, plain_text: &mut PlainText, bookmark: &Bookmark) {
+        // Copies of a url are told apart by their other fields
+        let same = |candidate: &Bookmark| &bookmark.clone().with_history_of(candidate) == candidate;
+        let stored = match self.bookmarks.get_mut(bookmark.url()) {
+            Some(primary) if same(primary) => Some(primary),
+            primary => self
+                .duplicates
+                .iter_mut()
+                .find(|duplicate| duplicate.url() == bookmark.url() && same(duplicate))
+                .or(primary),
+        };
+        if let Some(bookmark) = stored {
+            bookmark.visit(Bookmark::timestamp_now());
+            plain_text.increment_bookmarks_version();
+            plain_text.set_edited_true();
+        }
+    }
+
+    pub fn undo(&mut self, plain_text: &mut PlainText) -> Result<(), String> {
+        let edits = plain_text
+            .journal_mut()
+            .pop_undo()
+            .ok_or("Nothing to undo.")?;
+        if let Err(error) = self.revert(plain_text, &edits) {
+            plain_text.journal_mut().push_undo(edits);
+            return Err(error);
+        }
+        let reverted = plain_text.journal_mut().take_current();
+        plain_text.journal_mut().push_redo(reverted);
+        Ok(())
+    }
+
+    pub fn redo(&mut self, plain_text: &mut PlainText) -> Result<(), String> {
+        let edits = plain_text
+            .journal_mut()
+            .pop_redo()
+            .ok_or("Nothing to redo.")?;
+        if let Err(error) = self.revert(plain_text, &edits) {
+            plain_text.journal_mut().push_redo(edits);
+            return Err(error);
+        }
+        let reverted = plain_text.journal_mut().take_current();
+        plain_text.journal_mut().push_undo(reverted);
+        Ok(())
+    }
+
+    fn revert(&mut self, plain_text: &mut PlainText, edits: &[Edit]) -> Result<(), String> {
+        for edit in edits.iter().rev() {
+            if let Err(error) = self.revert_edit(plain_text, edit) {
+                // Put back what was already reverted so a failed revert changes nothing
+                let reverted = plain_text.journal_mut().take_current();
+                for edit in reverted.iter().rev() {
+                    let _ = self.revert_edit(plain_text, edit);
+                }
+                plain_text.journal_mut().take_current();
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    fn revert_edit(&mut self, plain_text: &mut PlainText, edit: &Edit) -> Result<(), String> {
+        let current = match edit.after() {
+            Some(after) => Some(
+                self.find(after)
+                    .cloned()
+                    .ok_or_else(|| format!("Bookmark was changed since: {}", after.url()))?,
+            ),
+            None => None,
+        };
+        match (edit.before(), current) {
+            (Some(before), Some(current)) => {
+                let before = before.clone().with_history_of(&current);
+                self.modify_bookmark(plain_text, before, &current);
+            }
+            (Some(before), None) => self.add_bookmark(plain_text, before.clone()),
+            (None, Some(current)) => self.remove_bookmark(plain_text, &current),
+            (None, None) => {}
+        }
+        Ok(())
+    }
+
+    fn find(&self, bookmark: &Bookmark) -> Option<&Bookmark> {
+        self.bookmarks
+            .get(bookmark.url())
+            .into_iter()
+            .chain(self.duplicates.iter())
+            .filter(|candidate| candidate.url() == bookmark.url())
+            .find(|candidate| &&bookmark.clone().with_history_of(candidate) == candidate)
+    }
+
+    fn store(&mut self, bookmark: Bookmark) {
+        if self.bookmarks.contains_key(bookmark.url()) {
+            self.duplicates.push(bookmark);
+        } else {
+            self.normalized_urls
+                .entry(url::normalize(bookmark.url()))
+                .or_default()
+                .push(bookmark.url().to_string());
+            self.bookmarks.insert(bookmark.url().to_string(), bookmark);
+        }
+    }
+
+    fn unstore(&mut self, bookmark: &Bookmark) -> Option<Bookmark> {
+        if self.bookmarks.get(bookmark.url()) != Some(bookmark) {
+            let index = self.duplicates.iter().position(|d| d == bookmark)?;
+            return Some(self.duplicates.remove(index));
+        }
+        let removed = self.bookmarks.remove(bookmark.url())?;
+        match self
+            .duplicates
+            .iter()
+            .position(|d| d.url() == bookmark.url())
+        {
+            Some(index) => {
+                let duplicate = self.duplicates.remove(index);
+                self.bookmarks
+                    .insert(duplicate.url().to_string(), duplicate);
+            }
+            None => {
+                let normalized_url = url::normalize(bookmark.url());
+                if let Some(urls) = self.normalized_urls.get_mut(&normalized_url) {
+                    urls.retain(|url| url != bookmark.url());
+                    if urls.is_empty() {
+                        self.normalized_urls.remove(&normalized_url);
+                    }
+                }
+            }
+        }
+        Some(removed)
+    }
+
+    pub fn merge_changes(
+        &self,
+        base: &ParsedFile,
+        merged: &mut ParsedFile,
+        plain_text: &mut PlainText,
+    ) -> Vec<String> {
+        let ours = self.url_groups();
+        let originals = base.url_groups();
+        let mut urls: Vec<&String> = ours.keys().chain(originals.keys()).collect();
+        urls.sort();
+        urls.dedup();
+
+        // Every copy of a url is merged together, so duplicates are kept too
+        let no_bookmarks = Vec::new();
+        let mut conflicts = Vec::new();
+        for url in urls {
+            let ours = ours.get(url).unwrap_or(&no_bookmarks);
+            let original = originals.get(url).unwrap_or(&no_bookmarks);
+            if ours == original {
+                continue;
+            }
+            let theirs = merged.copies(url);
+            if &theirs == ours {
+                continue;
+            }
+            if &theirs != original {
+                // Our version is kept as another copy, so neither side's edit is lost
+                let mut kept = false;
+                for bookmark in ours.iter().filter(|bookmark| !original.contains(bookmark)) {
+                    let same = |copy: &Bookmark| &bookmark.clone().with_history_of(copy) == copy;
+                    if !theirs.iter().any(same) {
+                        merged.add_bookmark(plain_text, bookmark.clone());
+                        kept = true;
+                    }
+                }
+                if kept {
+                    conflicts.push(url.clone());
+                }
+                continue;
+            }
+            match (theirs.as_slice(), ours.as_slice()) {
+                ([theirs], [ours]) => merged.modify_bookmark(plain_text, ours.clone(), theirs),
+                _ => {
+                    for bookmark in &theirs {
+                        merged.remove_bookmark(plain_text, bookmark);
+                    }
+                    for bookmark in ours {
+                        merged.add_bookmark(plain_text, bookmark.clone());
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+
+    fn url_groups(&self) -> HashMap<String, Vec<Bookmark>> {
+        self.bookmarks
+            .keys()
+            .map(|url| (url.clone(), self.copies(url)))
+            .collect()
+    }
+
+    // Sorted by line, so copies compare equal whatever order they were stored in
+    pub fn copies(&self, url: &str) -> Vec<Bookmark> {
+        let mut copies: Vec<Bookmark> = self
+            .bookmarks
+            .get(url)
+            .into_iter()
+            .chain(self.duplicates.iter().filter(|d| d.url() == url))
+            .cloned()
+            .collect();
+        copies.sort_by_cached_key(|bookmark| bookmark.to_line(0, 0));
+        copies
+    }
+
     pub fn add_category(&mut self, category: String) -> bool {
         match self.category_count.get_mut(&category) {
             Some(count) => {
@@ -126,7 +469,10 @@ impl ParsedFile {
             }
             None => {
                 self.category_count.insert(category.clone(), 1);
-                if let Err(index) = self.categories.binary_search(&category) {
+                if let Err(index) = self
+                    .categories
+                    .binary_search_by(|c| Self::name_order(c, &category))
+                {
                     self.add_category_char_count(&category);
                     self.categories.insert(index, category);
                     return true;
@@ -140,7 +486,11 @@ impl ParsedFile {
         if let Some(count) = self.category_count.get_mut(category) {
             *count -= 1;
             if *count == 0 {
-                if let Ok(index) = self.categories.binary_search(&category.to_string()) {
+                self.category_count.remove(category);
+                if let Ok(index) = self
+                    .categories
+                    .binary_search_by(|c| Self::name_order(c, category))
+                {
                     self.remove_category_char_count(category);
                     self.categories.remove(index);
                     return true;
@@ -150,15 +500,66 @@ impl ParsedFile {
         false
     }
 
+    pub fn add_tags(&mut self, tags: &[String]) {
+        for tag in tags {
+            match self.tag_count.get_mut(tag) {
+                Some(count) => {
+                    *count += 1;
+                }
+                None => {
+                    self.tag_count.insert(tag.clone(), 1);
+                    if let Err(index) = self.tags.binary_search_by(|t| Self::name_order(t, tag)) {
+                        self.tags.insert(index, tag.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn remove_tags(&mut self, tags: &[String]) {
+        for tag in tags {
+            if let Some(count) = self.tag_count.get_mut(tag) {
+                *count -= 1;
+                if *count == 0 {
+                    self.tag_count.remove(tag);
+                    if let Ok(index) = self.tags.binary_search_by(|t| Self::name_order(t, tag)) {
+                        self.tags.remove(index);
+                    }
+                }
+            }
+        }
+    }
+
+    // Names that only differ in case or punctuation are still kept apart
+    fn name_order(a: &str, b: &str) -> std::cmp::Ordering {
+        PlainText::alphabetic_sort(a, b).then_with(|| a.cmp(b))
+    }
+
     pub fn add_bookmark_option_string(&self) -> String {
+        self.option_string(ADD_BOOKMARK)
+    }
+
+    pub fn filter_by_tag_option_string(&self) -> String {
+        self.option_string(FILTER_BY_TAG)
+    }
+
+    pub fn undo_option_string(&self) -> String {
+        self.option_string(UNDO_LAST_CHANGE)
+    }
+
+    pub fn redo_option_string(&self) -> String {
+        self.option_string(REDO_LAST_CHANGE)
+    }
+
+    fn option_string(&self, option: &str) -> String {
         let padding = (self.longest_title + self.longest_category + 11)
-            .saturating_sub(ADD_BOOKMARK.chars().count());
+            .saturating_sub(option.chars().count());
         let left_padding = padding / 2;
         let right_padding = padding - left_padding;
         format!(
             "{}{}{}",
             SEPARATOR_LINE_SYMBOL.repeat(left_padding),
-            ADD_BOOKMARK,
+            option,
             SEPARATOR_LINE_SYMBOL.repeat(right_padding)
         )
     }
@@ -188,7 +589,8 @@ impl ParsedFile {
     }
 
     fn add_char_count(char_count_vec: &mut [usize], longest: &mut usize, field: &str) {
-        let char_count = field.chars().count();
+        // Menu lines truncate long fields, so they are counted at the maximum
+        let char_count = field.chars().count().min(char_count_vec.len() - 1);
         if char_count == 0 {
             return;
         }
@@ -203,7 +605,7 @@ impl ParsedFile {
     }
 
     fn remove_char_count(char_count_vec: &mut [usize], longest: &mut usize, field: &str) {
-        let char_count = field.chars().count();
+        let char_count = field.chars().count().min(char_count_vec.len() - 1);
         if char_count == 0 {
             return;
         }
@@ -254,14 +656,49 @@ mod tests {
         assert_eq!(parsed_file.categories().len(), 1);
         assert!(plain_text.edited());
         assert_eq!(parsed_file.longest_title, char_count);
+        assert!(parsed_file.bookmarks[Bookmark::default().url()]
+            .added()
+            .is_some());
+    }
+
+    #[test]
+    fn test_parsed_file_visit_bookmark() {
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let file = Bookmark::default().to_line(0, 0);
+        let mut parsed_file = ParsedFile::new(&file);
+        let url = Bookmark::default().url().to_string();
+        parsed_file.visit_bookmark(&mut plain_text, &Bookmark::default());
+        parsed_file.visit_bookmark(&mut plain_text, &Bookmark::default());
+        let bookmark = &parsed_file.bookmarks[&url];
+        assert_eq!(bookmark.visit_count(), 2);
+        assert!(bookmark.last_visited().is_some());
+        assert!(plain_text.edited());
+
+        // Test that visiting a duplicate updates that copy
+        let duplicate = Bookmark::new("copy".to_string(), "category".to_string(), url.clone());
+        parsed_file.add_bookmark(&mut plain_text, duplicate.clone());
+        parsed_file.visit_bookmark(&mut plain_text, &duplicate);
+        assert_eq!(parsed_file.bookmarks[&url].visit_count(), 2);
+        assert_eq!(parsed_file.duplicates[0].visit_count(), 1);
+
+        // Test that menu lines are mapped back to the stored copy with its history
+        let menu_line = parsed_file.duplicates[0].to_menu_line(0, 0);
+        let menu_bookmark = Bookmark::from_line(&menu_line).unwrap();
+        assert_eq!(menu_bookmark.visit_count(), 0);
+        let stored = parsed_file.find_menu_bookmark(&menu_bookmark).unwrap();
+        assert!(stored == &parsed_file.duplicates[0]);
+        let menu_line = Bookmark::default().to_menu_line(0, 0);
+        let menu_bookmark = Bookmark::from_line(&menu_line).unwrap();
+        let stored = parsed_file.find_menu_bookmark(&menu_bookmark).unwrap();
+        assert_eq!(stored.visit_count(), 2);
     }
 
     #[test]
     fn test_parsed_file_modify_bookmark() {
         let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
         let mut parsed_file = ParsedFile::new(plain_text.bookmarks());
-        let old_bookmark = Bookmark::default();
-        parsed_file.add_bookmark(&mut plain_text, old_bookmark.clone());
+        parsed_file.add_bookmark(&mut plain_text, Bookmark::default());
+        let old_bookmark = parsed_file.bookmarks[Bookmark::default().url()].clone();
         let title = "new title".to_string();
         let category = "new category".to_string();
         let url = "new url".to_string();
@@ -288,13 +725,264 @@ mod tests {
         let char_count = bookmark.title().chars().count();
         let url = bookmark.url().to_string();
         parsed_file.add_bookmark(&mut plain_text, bookmark);
-        parsed_file.remove_bookmark(&mut plain_text, &url);
+        let bookmark = parsed_file.bookmarks[&url].clone();
+        parsed_file.remove_bookmark(&mut plain_text, &bookmark);
         assert!(parsed_file.bookmarks.is_empty());
         assert!(parsed_file.categories().is_empty());
         assert!(plain_text.edited());
         assert_ne!(parsed_file.longest_title, char_count);
     }
 
+    #[test]
+    fn test_parsed_file_duplicates() {
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let bookmark = |title: &str| {
+            Bookmark::new(title.to_string(), title.to_string(), "url".to_string())
+                .with_added(Some(1))
+        };
+        let file = format!(
+            "{}{}",
+            bookmark("first").to_line(0, 0),
+            bookmark("second").to_line(0, 0)
+        );
+        let mut parsed_file = ParsedFile::new(&file);
+        assert_eq!(parsed_file.bookmarks["url"].title(), "first");
+        assert_eq!(parsed_file.duplicates().len(), 1);
+        assert_eq!(parsed_file.duplicate_urls(), ["url"]);
+        assert_eq!(parsed_file.all_bookmarks().count(), 2);
+        assert_eq!(parsed_file.categories().len(), 2);
+
+        // Test that adding an existing url keeps both
+        parsed_file.add_bookmark(&mut plain_text, bookmark("third"));
+        assert_eq!(parsed_file.duplicates().len(), 2);
+
+        // Test that the exact duplicate is modified and removed
+        parsed_file.modify_bookmark(&mut plain_text, bookmark("changed"), &bookmark("second"));
+        assert_eq!(parsed_file.bookmarks["url"].title(), "first");
+        assert_eq!(parsed_file.duplicates()[1].title(), "changed");
+        parsed_file.remove_bookmark(&mut plain_text, &bookmark("changed"));
+        assert_eq!(parsed_file.duplicates().len(), 1);
+        assert_eq!(parsed_file.categories(), &["first", "third"]);
+
+        // Test that removing the first bookmark keeps the duplicate
+        parsed_file.remove_bookmark(&mut plain_text, &bookmark("first"));
+        assert_eq!(parsed_file.bookmarks["url"].title(), "third");
+        assert!(parsed_file.duplicates().is_empty());
+
+        // Test that merging keeps the existing fields and combines the rest
+        let merged = bookmark("merged")
+            .with_tags(vec!["tag".to_string()])
+            .with_description("description")
+            .with_visits(Some(5), 2);
+        parsed_file.add_bookmark_or_resolve(&mut plain_text, merged, &OnDuplicate::Merge);
+        let bookmark = &parsed_file.bookmarks["url"];
+        assert_eq!(bookmark.title(), "third");
+        assert_eq!(bookmark.tags(), ["tag"]);
+        assert_eq!(bookmark.description(), "description");
+        assert_eq!(bookmark.visit_count(), 2);
+        assert!(parsed_file.duplicates().is_empty());
+
+        // Test that urls are compared after normalization
+        let near_duplicate = Bookmark::new(
+            "title".to_string(),
+            "category".to_string(),
+            "url/#top".to_string(),
+        );
+        assert_eq!(parsed_file.find_duplicate("url/#top").unwrap().url(), "url");
+        assert!(!parsed_file.add_bookmark_or_resolve(
+            &mut plain_text,
+            near_duplicate.clone(),
+            &OnDuplicate::Skip
+        ));
+        parsed_file.add_bookmark_or_resolve(
+            &mut plain_text,
+            near_duplicate,
+            &OnDuplicate::KeepBoth,
+        );
+        assert_eq!(parsed_file.bookmarks.len(), 2);
+        assert_eq!(parsed_file.duplicate_groups().len(), 1);
+        parsed_file.remove_bookmark(&mut plain_text, &parsed_file.bookmarks["url"].clone());
+        assert_eq!(parsed_file.find_duplicate("url").unwrap().url(), "url/#top");
+    }
+
+    #[test]
+    fn test_parsed_file_tags() {
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
+        let bookmark = Bookmark::default().with_tags(tags(&["rust", "cli"]));
+        let file = bookmark.to_line(0, 0);
+        let mut parsed_file = ParsedFile::new(&file);
+        assert_eq!(parsed_file.tags(), &tags(&["cli", "rust"]));
+
+        let other_bookmark = Bookmark::new(
+            "Docs".to_string(),
+            "Development".to_string(),
+            "https://docs.rs/".to_string(),
+        )
+        .with_tags(tags(&["rust", "docs"]));
+        parsed_file.add_bookmark(&mut plain_text, other_bookmark.clone());
+        assert_eq!(parsed_file.tags(), &tags(&["cli", "docs", "rust"]));
+        let other_bookmark = parsed_file.bookmarks[other_bookmark.url()].clone();
+
+        let modified_bookmark = other_bookmark.clone().with_tags(tags(&["reference"]));
+        parsed_file.modify_bookmark(&mut plain_text, modified_bookmark.clone(), &other_bookmark);
+        assert_eq!(parsed_file.tags(), &tags(&["cli", "reference", "rust"]));
+
+        parsed_file.remove_bookmark(&mut plain_text, &bookmark);
+        assert_eq!(parsed_file.tags(), &tags(&["reference"]));
+
+        // Test that tags and categories that differ in case are added and removed once
+        let mixed_case = Bookmark::new(
+            "Mixed".to_string(),
+            "Rust".to_string(),
+            "https://example.com/".to_string(),
+        )
+        .with_tags(tags(&["Zig", "api", "Rust", "rust"]));
+        let lowercase = Bookmark::default().with_tags(tags(&["rust"]));
+        parsed_file.add_bookmark(&mut plain_text, mixed_case.clone());
+        parsed_file.add_bookmark(&mut plain_text, lowercase.clone());
+        let mixed_case = parsed_file.bookmarks[mixed_case.url()].clone();
+        let lowercase = parsed_file.bookmarks[lowercase.url()].clone();
+        assert_eq!(
+            parsed_file.tags(),
+            &tags(&["api", "reference", "Rust", "rust", "Zig"])
+        );
+        parsed_file.remove_bookmark(&mut plain_text, &mixed_case);
+        assert_eq!(parsed_file.tags(), &tags(&["reference", "rust"]));
+        parsed_file.remove_bookmark(&mut plain_text, &lowercase);
+        parsed_file.remove_bookmark(&mut plain_text, &modified_bookmark);
+        assert!(parsed_file.tags().is_empty());
+        assert!(parsed_file.categories().is_empty());
+    }
+
+    #[test]
+    fn test_parsed_file_undo() {
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let mut parsed_file = ParsedFile::new(&Bookmark::default().to_line(0, 0));
+        let default_url = Bookmark::default().url().to_string();
+        assert!(parsed_file.undo(&mut plain_text).is_err());
+
+        let new_bookmark = Bookmark::new(
+            "title".to_string(),
+            "category".to_string(),
+            "url".to_string(),
+        );
+        parsed_file.add_bookmark(&mut plain_text, new_bookmark);
+        plain_text.journal_mut().commit();
+        let old_bookmark = parsed_file.bookmarks[&default_url].clone();
+        let new_bookmark = Bookmark::new(
+            "new title".to_string(),
+            "new category".to_string(),
+            "new url".to_string(),
+        )
+        .with_history_of(&old_bookmark);
+        parsed_file.modify_bookmark(&mut plain_text, new_bookmark, &old_bookmark);
+
+        // Test that the uncommitted modification is undone first
+        assert!(parsed_file.undo(&mut plain_text).is_ok());
+        assert!(parsed_file.bookmarks.contains_key(&default_url));
+        assert!(!parsed_file.bookmarks.contains_key("new url"));
+        assert_eq!(parsed_file.categories().len(), 2);
+        assert!(parsed_file.undo(&mut plain_text).is_ok());
+        assert_eq!(parsed_file.bookmarks.len(), 1);
+        assert!(parsed_file.undo(&mut plain_text).is_err());
+
+        assert!(parsed_file.redo(&mut plain_text).is_ok());
+        assert!(parsed_file.bookmarks.contains_key("url"));
+        assert!(parsed_file.redo(&mut plain_text).is_ok());
+        assert_eq!(parsed_file.bookmarks["new url"].title(), "new title");
+        assert!(parsed_file.redo(&mut plain_text).is_err());
+
+        // Test that an edit changed since is not undone
+        let visited = parsed_file.bookmarks["new url"].clone();
+        parsed_file.visit_bookmark(&mut plain_text, &visited);
+        let bookmark = parsed_file.bookmarks["url"].clone();
+        parsed_file.remove_bookmark(&mut plain_text, &bookmark);
+        plain_text.journal_mut().commit();
+        let old_bookmark = parsed_file.bookmarks["new url"].clone();
+        let new_bookmark = Bookmark::new(
+            "other title".to_string(),
+            "new category".to_string(),
+            "new url".to_string(),
+        );
+        parsed_file
+            .bookmarks
+            .insert("new url".to_string(), new_bookmark);
+        assert!(parsed_file.undo(&mut plain_text).is_ok());
+        assert!(parsed_file.undo(&mut plain_text).is_err());
+        assert_eq!(parsed_file.bookmarks.len(), 2);
+        parsed_file
+            .bookmarks
+            .insert("new url".to_string(), old_bookmark);
+        assert!(parsed_file.undo(&mut plain_text).is_ok());
+        assert_eq!(parsed_file.bookmarks[&default_url].visit_count(), 1);
+    }
+
+    #[test]
+    fn test_parsed_file_merge_changes() {
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let bookmark = |title: &str, url: &str| {
+            Bookmark::new(title.to_string(), "category".to_string(), url.to_string())
+                .with_added(Some(1))
+        };
+        let to_file = |bookmarks: &[Bookmark]| {
+            bookmarks
+                .iter()
+                .map(|bookmark| bookmark.to_line(0, 0))
+                .collect::<String>()
+        };
+
+        let base = ParsedFile::new(&to_file(&[
+            bookmark("a", "url_a"),
+            bookmark("b", "url_b"),
+            bookmark("c", "url_c"),
+        ]));
+        // Ours modifies a, removes b and adds d
+        let ours = ParsedFile::new(&to_file(&[
+            bookmark("A", "url_a"),
+            bookmark("c", "url_c"),
+            bookmark("d", "url_d"),
+        ]));
+        // Theirs modifies c and adds e
+        let mut merged = ParsedFile::new(&to_file(&[
+            bookmark("a", "url_a"),
+            bookmark("b", "url_b"),
+            bookmark("C", "url_c"),
+            bookmark("e", "url_e"),
+        ]));
+        let conflicts = ours.merge_changes(&base, &mut merged, &mut plain_text);
+        assert!(conflicts.is_empty());
+        let mut titles: Vec<&str> = merged.bookmarks.values().map(|b| b.title()).collect();
+        titles.sort();
+        assert_eq!(titles, ["A", "C", "d", "e"]);
+
+        // Test that a bookmark changed on both sides is reported
+        let mut merged = ParsedFile::new(&to_file(&[
+            bookmark("theirs", "url_a"),
+            bookmark("c", "url_c"),
+        ]));
+        let conflicts = ours.merge_changes(&base, &mut merged, &mut plain_text);
+        assert_eq!(conflicts, ["url_a"]);
+
+        // Test that a duplicate kept in this session survives the merge
+        let ours = ParsedFile::new(&to_file(&[
+            bookmark("a", "url_a"),
+            bookmark("a copy", "url_a"),
+            bookmark("b", "url_b"),
+            bookmark("c", "url_c"),
+        ]));
+        let mut merged = ParsedFile::new(&to_file(&[
+            bookmark("a", "url_a"),
+            bookmark("b", "url_b"),
+            bookmark("C", "url_c"),
+        ]));
+        let conflicts = ours.merge_changes(&base, &mut merged, &mut plain_text);
+        assert!(conflicts.is_empty());
+        let mut titles: Vec<&str> = merged.all_bookmarks().map(|b| b.title()).collect();
+        titles.sort();
+        assert_eq!(titles, ["C", "a", "a copy", "b"]);
+    }
+
     #[test]
     fn test_parsed_file_add_category() {
         let mut parsed_file = ParsedFile::new("test");
@@ -352,4 +1040,51 @@ mod tests {
         ParsedFile::remove_char_count(&mut char_count_vec, &mut longest, field1);
         assert_eq!(longest, 0);
     }
+
+    #[test]
+    fn test_parsed_file_long_title_char_count() {
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let mut parsed_file = ParsedFile::new(plain_text.bookmarks());
+        parsed_file.add_bookmark(&mut plain_text, Bookmark::default());
+        let short_title = Bookmark::default().title().chars().count();
+        let long_bookmark = Bookmark::new(
+            "t".repeat(TITLE_MAX_LENGTH + 10),
+            "c".repeat(CATEGORY_MAX_LENGTH + 10),
+            "https://long.com/".to_string(),
+        );
+        parsed_file.add_bookmark(&mut plain_text, long_bookmark.clone());
+        assert_eq!(parsed_file.longest_title, TITLE_MAX_LENGTH);
+        assert_eq!(parsed_file.longest_category, CATEGORY_MAX_LENGTH);
+
+        // Test that the padding shrinks again once the long title is gone
+        let stored = parsed_file.bookmarks[long_bookmark.url()].clone();
+        parsed_file.remove_bookmark(&mut plain_text, &stored);
+        assert_eq!(parsed_file.longest_title, short_title);
+        assert_eq!(
+            parsed_file.longest_category,
+            Bookmark::default().category().chars().count()
+        );
+    }
+
+    #[test]
+    fn test_parsed_file_remove_missing_copy() {
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let mut parsed_file = ParsedFile::new(plain_text.bookmarks());
+        parsed_file.add_bookmark(&mut plain_text, Bookmark::default());
+        let url = Bookmark::default().url().to_string();
+        let stored = parsed_file.bookmarks[&url].clone();
+        let missing = Bookmark::new("other".to_string(), "other".to_string(), url.clone());
+
+        // Test that a bookmark sharing only the url leaves the stored copy alone
+        parsed_file.remove_bookmark(&mut plain_text, &missing);
+        assert!(parsed_file.bookmarks[&url] == stored);
+        let renamed = Bookmark::new("renamed".to_string(), "other".to_string(), url.clone());
+        parsed_file.modify_bookmark(&mut plain_text, renamed, &missing);
+        assert!(parsed_file.bookmarks[&url] == stored);
+        assert_eq!(parsed_file.all_bookmarks().count(), 1);
+        assert_eq!(parsed_file.categories().len(), 1);
+
+        parsed_file.remove_bookmark(&mut plain_text, &stored);
+        assert!(parsed_file.bookmarks.is_empty());
+    }
 }
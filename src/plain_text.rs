@@ -1,13 +1,40 @@
 use std::cmp::Ordering;
-use std::fs;
-use std::path::PathBuf;
-
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::backup::Backups;
+use crate::bookmark::Bookmark;
+use crate::journal::Journal;
 use crate::{parsed_file::*, SEPARATOR_LINE_SYMBOL};
 
 const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+// The lock file is kept next to the bookmark file, removing it while another writer waits on it
+// would let a third writer in
+const LOCK_FILE_EXTENSION: &str = "lock";
+const JOURNAL_FILE_EXTENSION: &str = "journal";
+
+pub const SUPPORTED_SORT_ORDERS: [&str; 2] = ["alphabetic", "frecency"];
+
+pub enum SortOrder {
+    Alphabetic,
+    Frecency,
+}
+
+impl SortOrder {
+    pub fn new(sort_order: &str) -> Result<Self, String> {
+        match sort_order {
+            "alphabetic" => Ok(Self::Alphabetic),
+            "frecency" => Ok(Self::Frecency),
+            _ => Err(format!("Unsupported sort order: {}", sort_order)),
+        }
+    }
+}
 
 pub struct PlainText {
     file_path: PathBuf,
+    original_bookmarks: String,
     bookmarks: String,
     previous_bookmarks_version: usize,
     current_bookmarks_version: usize,
@@ -16,13 +43,19 @@ pub struct PlainText {
     previous_categories_version: usize,
     current_categories_version: usize,
     categories_initialized: bool,
+    sort_order: SortOrder,
+    backups: Backups,
+    journal: Journal,
+    original_journal: Journal,
     edited: bool,
 }
 
 impl PlainText {
     pub fn new(file_path: PathBuf) -> Self {
         Self {
+            backups: Backups::new(&file_path, None, 0),
             file_path,
+            original_bookmarks: String::new(),
             bookmarks: String::new(),
             previous_bookmarks_version: 0,
             current_bookmarks_version: 0,
@@ -31,6 +64,9 @@ impl PlainText {
             previous_categories_version: 0,
             current_categories_version: 0,
             categories_initialized: false,
+            sort_order: SortOrder::Alphabetic,
+            journal: Journal::new(),
+            original_journal: Journal::new(),
             edited: false,
         }
     }
@@ -43,6 +79,30 @@ impl PlainText {
         &self.categories
     }
 
+    pub fn sort_order(&self) -> &SortOrder {
+        &self.sort_order
+    }
+
+    pub fn set_sort_order(&mut self, sort_order: SortOrder) {
+        self.sort_order = sort_order;
+    }
+
+    pub fn backups(&self) -> &Backups {
+        &self.backups
+    }
+
+    pub fn set_backups(&mut self, backups: Backups) {
+        self.backups = backups;
+    }
+
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    pub fn journal_mut(&mut self) -> &mut Journal {
+        &mut self.journal
+    }
+
     #[allow(dead_code)]
     pub fn edited(&self) -> bool {
         self.edited
@@ -61,11 +121,38 @@ impl PlainText {
     }
 
     pub fn read(&mut self) -> Result<(), String> {
-        if fs::metadata(&self.file_path)
+        self.bookmarks = Self::read_file(&self.file_path)?;
+        self.original_bookmarks = self.bookmarks.clone();
+        self.journal = self.read_journal()?;
+        self.original_journal = self.journal.clone();
+
+        Ok(())
+    }
+
+    fn read_journal(&self) -> Result<Journal, String> {
+        let journal_path = self.journal_path();
+        match journal_path.exists() {
+            true => Ok(Journal::parse(&Self::read_file(&journal_path)?)),
+            false => Ok(Journal::new()),
+        }
+    }
+
+    fn journal_path(&self) -> PathBuf {
+        let file_path =
+            fs::canonicalize(&self.file_path).unwrap_or_else(|_| self.file_path.clone());
+        let file_name = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        file_path.with_file_name(format!(".{}.{}", file_name, JOURNAL_FILE_EXTENSION))
+    }
+
+    fn read_file(file_path: &Path) -> Result<String, String> {
+        if fs::metadata(file_path)
             .map_err(|error| {
                 format!(
                     "Failed to read bookmark file {}: {}",
-                    self.file_path.display(),
+                    file_path.display(),
                     error
                 )
             })?
@@ -75,33 +162,166 @@ impl PlainText {
             return Err(format!(
                 "File larger than {} megabytes: {}",
                 MAX_FILE_SIZE / 1024 / 1024,
-                self.file_path.display()
+                file_path.display()
             ));
         }
 
-        self.bookmarks = fs::read_to_string(&self.file_path).map_err(|error| {
+        fs::read_to_string(file_path).map_err(|error| {
             format!(
                 "Failed to read bookmark file {}: {}",
-                self.file_path.display(),
+                file_path.display(),
                 error
             )
-        })?;
-
-        Ok(())
+        })
     }
 
     pub fn write(&mut self, parsed_file: &ParsedFile) -> Result<(), String> {
         if !self.edited {
             return Ok(());
         }
-        self.update_bookmarks(parsed_file);
-        fs::write(&self.file_path, &self.bookmarks).map_err(|error| {
+
+        // Held until the end of this function so concurrent writers take turns.
+        let _lock = self.lock()?;
+
+        let mut conflicts = Vec::new();
+        let current_bookmarks = match self.file_path.exists() {
+            true => Self::read_file(&self.file_path)?,
+            false => String::new(),
+        };
+        if current_bookmarks == self.original_bookmarks {
+            self.update_bookmarks(parsed_file);
+        } else {
+            // Our edits are already journaled, so the ones replayed by the merge are dropped
+            let journal = std::mem::replace(&mut self.journal, Journal::new());
+            let base = ParsedFile::new(&self.original_bookmarks);
+            let mut merged = ParsedFile::new(&current_bookmarks);
+            // Bookmarks edited in both places keep both versions, everything else is saved
+            conflicts = parsed_file.merge_changes(&base, &mut merged, self);
+            self.journal = journal.merge(&self.original_journal, self.read_journal()?);
+            self.increment_bookmarks_version();
+            self.update_bookmarks(&merged);
+        }
+
+        // Opening a bookmark only updates its history, which is not worth a backup
+        if Self::without_history(&current_bookmarks) != Self::without_history(&self.bookmarks) {
+            self.backups.create(&current_bookmarks).map_err(|error| {
+                format!(
+                    "Failed to back up bookmark file to {}: {}",
+                    self.backups.directory().display(),
+                    error
+                )
+            })?;
+        }
+
+        Self::write_atomically(&self.file_path, &self.bookmarks).map_err(|error| {
             format!(
                 "Failed to write bookmark file {}: {}",
                 self.file_path.display(),
                 error
             )
-        })
+        })?;
+        self.original_bookmarks = self.bookmarks.clone();
+
+        self.journal.commit();
+        self.original_journal = self.journal.clone();
+        let journal_path = self.journal_path();
+        Self::write_atomically(&journal_path, &self.journal.render()).map_err(|error| {
+            format!(
+                "Failed to write journal {}: {}",
+                journal_path.display(),
+                error
+            )
+        })?;
+
+        match conflicts.is_empty() {
+            true => Ok(()),
+            false => Err(format!(
+                "Bookmark file {} was changed by another program and these bookmarks were edited in both places: {}. Both versions were kept as copies of the URL, your other changes were saved.",
+                self.file_path.display(),
+                conflicts.join(", ")
+            )),
+        }
+    }
+
+    fn without_history(bookmarks: &str) -> Vec<String> {
+        let mut lines: Vec<String> = bookmarks
+            .lines()
+            .map(|line| match Bookmark::from_line(line) {
+                Some(bookmark) => bookmark.with_visits(None, 0).to_line(0, 0),
+                None => format!("{}\n", line),
+            })
+            .collect();
+        // Sorting by frecency reorders the lines when a bookmark is opened
+        lines.sort();
+        lines
+    }
+
+    fn lock(&self) -> Result<File, String> {
+        let mut lock_path = self.file_path.clone().into_os_string();
+        lock_path.push(format!(".{}", LOCK_FILE_EXTENSION));
+        let lock_path = PathBuf::from(lock_path);
+
+        let lock_file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|error| {
+                format!(
+                    "Failed to open lock file {}: {}",
+                    lock_path.display(),
+                    error
+                )
+            })?;
+        lock_file.lock().map_err(|error| {
+            format!(
+                "Failed to lock bookmark file {}: {}",
+                self.file_path.display(),
+                error
+            )
+        })?;
+
+        Ok(lock_file)
+    }
+
+    fn write_atomically(file_path: &Path, contents: &str) -> std::io::Result<()> {
+        // Resolve symlinks so the rename replaces the real file instead of the link.
+        let file_path = fs::canonicalize(file_path).unwrap_or_else(|_| file_path.to_path_buf());
+        let directory = match file_path.parent() {
+            Some(directory) if !directory.as_os_str().is_empty() => directory.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let file_name = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let temp_path = directory.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+        if let Err(error) = Self::replace_with_temp_file(&temp_path, &file_path, contents) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(error);
+        }
+
+        // Persist the rename itself; not every platform can sync a directory.
+        if let Ok(directory) = File::open(&directory) {
+            let _ = directory.sync_all();
+        }
+
+        Ok(())
+    }
+
+    fn replace_with_temp_file(
+        temp_path: &Path,
+        file_path: &Path,
+        contents: &str,
+    ) -> std::io::Result<()> {
+        let mut temp_file = File::create(temp_path)?;
+        temp_file.write_all(contents.as_bytes())?;
+        if let Ok(metadata) = fs::metadata(file_path) {
+            temp_file.set_permissions(metadata.permissions())?;
+        }
+        temp_file.sync_all()?;
+        fs::rename(temp_path, file_path)
     }
 
     pub fn update_bookmarks(&mut self, parsed_file: &ParsedFile) {
@@ -111,45 +331,82 @@ impl PlainText {
             return;
         };
 
-        self.bookmarks.clear();
+        self.bookmarks = Self::render_lines(parsed_file, Bookmark::to_line);
+
+        self.previous_bookmarks_version = self.current_bookmarks_version;
+        self.bookmarks_initialized = true;
+    }
 
-        let mut bookmarks_vec: Vec<_> = parsed_file.bookmarks.values().collect();
+    fn render_lines(
+        parsed_file: &ParsedFile,
+        to_line: impl Fn(&Bookmark, usize, usize) -> String,
+    ) -> String {
+        let mut lines = String::new();
+        let mut bookmarks_vec: Vec<_> = parsed_file.all_bookmarks().collect();
         let separator_line = format!(
             "{}\n",
             SEPARATOR_LINE_SYMBOL
                 .repeat(parsed_file.longest_title + parsed_file.longest_category + 11)
         );
 
-        bookmarks_vec.sort_by(|a, b| {
-            let cat_ordering = Self::alphabetic_sort(a.category(), b.category());
-            if cat_ordering == Ordering::Equal {
-                Self::alphabetic_sort(a.title(), b.title())
-            } else {
-                cat_ordering
-            }
-        });
+        Self::sort_bookmarks(&mut bookmarks_vec);
 
         let mut current_category = None;
         let combined_len = bookmarks_vec.len() + parsed_file.invalid_lines.len();
         for i in 0..combined_len {
             if let Some(line) = parsed_file.invalid_lines.get(&i) {
-                self.bookmarks.push_str(&format!("{}\n", line));
+                lines.push_str(&format!("{}\n", line));
             } else if i < bookmarks_vec.len() {
                 if let Some(cat) = current_category {
                     if cat != bookmarks_vec[i].category() {
-                        self.bookmarks.push_str(&separator_line);
+                        lines.push_str(&separator_line);
                     }
                 }
                 current_category = Some(bookmarks_vec[i].category());
-                self.bookmarks.push_str(
-                    &bookmarks_vec[i]
-                        .to_line(parsed_file.longest_title, parsed_file.longest_category),
-                );
+                lines.push_str(&to_line(
+                    bookmarks_vec[i],
+                    parsed_file.longest_title,
+                    parsed_file.longest_category,
+                ));
             }
         }
+        lines
+    }
 
-        self.previous_bookmarks_version = self.current_bookmarks_version;
-        self.bookmarks_initialized = true;
+    pub fn menu_bookmarks(&self, parsed_file: &ParsedFile) -> String {
+        match self.sort_order {
+            SortOrder::Alphabetic => Self::render_lines(parsed_file, Bookmark::to_menu_line),
+            SortOrder::Frecency => {
+                let mut bookmarks_vec: Vec<&Bookmark> = parsed_file.all_bookmarks().collect();
+                Self::sort_bookmarks_by(&mut bookmarks_vec, &self.sort_order);
+                bookmarks_vec
+                    .iter()
+                    .map(|bookmark| {
+                        bookmark
+                            .to_menu_line(parsed_file.longest_title, parsed_file.longest_category)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    pub fn menu_previews(parsed_file: &ParsedFile) -> HashMap<String, String> {
+        parsed_file
+            .all_bookmarks()
+            .map(|bookmark| {
+                let line =
+                    bookmark.to_menu_line(parsed_file.longest_title, parsed_file.longest_category);
+                (line.trim_end_matches('\n').to_string(), bookmark.details())
+            })
+            .collect()
+    }
+
+    pub fn lines_with_tag(lines: &str, tag: &str) -> String {
+        lines
+            .lines()
+            .filter(|line| Bookmark::from_line(line).is_some_and(|bookmark| bookmark.has_tag(tag)))
+            .map(|line| format!("{}\n", line))
+            .collect()
     }
 
     pub fn update_categories(&mut self, parsed_file: &ParsedFile) {
@@ -169,6 +426,25 @@ impl PlainText {
         self.categories_initialized = true;
     }
 
+    pub fn sort_bookmarks(bookmarks: &mut [&Bookmark]) {
+        bookmarks.sort_by(|a, b| {
+            let cat_ordering = Self::alphabetic_sort(a.category(), b.category());
+            if cat_ordering == Ordering::Equal {
+                Self::alphabetic_sort(a.title(), b.title())
+            } else {
+                cat_ordering
+            }
+        });
+    }
+
+    pub fn sort_bookmarks_by(bookmarks: &mut [&Bookmark], sort_order: &SortOrder) {
+        Self::sort_bookmarks(bookmarks);
+        if let SortOrder::Frecency = sort_order {
+            let now = Bookmark::timestamp_now();
+            bookmarks.sort_by_key(|bookmark| std::cmp::Reverse(bookmark.frecency(now)));
+        }
+    }
+
     pub fn alphabetic_sort(a: &str, b: &str) -> Ordering {
         let a = a
             .chars()
@@ -186,10 +462,6 @@ impl PlainText {
 
 #[cfg(test)]
 mod tests {
-    use std::fs::File;
-
-    use crate::bookmark::Bookmark;
-
     use super::*;
 
     #[test]
@@ -209,6 +481,137 @@ mod tests {
         assert!(plain_text.write(&parsed_file).is_ok());
     }
 
+    #[test]
+    fn test_plain_text_write_merges_changes() {
+        let path = std::env::temp_dir().join(format!("fmark_merge_{}.txt", std::process::id()));
+        let bookmark = |title: &str, url: &str| {
+            Bookmark::new(title.to_string(), "category".to_string(), url.to_string())
+                .with_added(Some(1))
+        };
+        let file = [bookmark("a", "url_a"), bookmark("b", "url_b")]
+            .iter()
+            .map(|bookmark| bookmark.to_line(0, 0))
+            .collect::<String>();
+        fs::write(&path, &file).unwrap();
+
+        let mut plain_text = PlainText::new(path.clone());
+        plain_text.read().unwrap();
+        let mut parsed_file = ParsedFile::new(plain_text.bookmarks());
+        parsed_file.add_bookmark(&mut plain_text, bookmark("c", "url_c"));
+
+        // Another program changes a different bookmark in the meantime
+        let changed_file = [bookmark("a", "url_a"), bookmark("B", "url_b")]
+            .iter()
+            .map(|bookmark| bookmark.to_line(0, 0))
+            .collect::<String>();
+        fs::write(&path, &changed_file).unwrap();
+
+        assert!(plain_text.write(&parsed_file).is_ok());
+        let merged = ParsedFile::new(&fs::read_to_string(&path).unwrap());
+        assert_eq!(merged.bookmarks.len(), 3);
+        assert_eq!(merged.bookmarks["url_b"].title(), "B");
+        assert!(merged.bookmarks.contains_key("url_c"));
+
+        // Test that a bookmark edited in both places keeps both versions and is reported
+        let mut parsed_file = merged;
+        let old_bookmark = parsed_file.bookmarks["url_a"].clone();
+        parsed_file.modify_bookmark(&mut plain_text, bookmark("ours", "url_a"), &old_bookmark);
+        parsed_file.add_bookmark(&mut plain_text, bookmark("d", "url_d"));
+        let conflicting_file = [bookmark("theirs", "url_a")]
+            .iter()
+            .map(|bookmark| bookmark.to_line(0, 0))
+            .collect::<String>();
+        fs::write(&path, &conflicting_file).unwrap();
+        let result = plain_text.write(&parsed_file);
+        assert!(result.is_err_and(|error| error.contains(": url_a.")));
+        let saved = ParsedFile::new(&fs::read_to_string(&path).unwrap());
+        assert_eq!(saved.bookmarks.len(), 2);
+        let mut titles: Vec<String> = saved
+            .copies("url_a")
+            .iter()
+            .map(|bookmark| bookmark.title().to_string())
+            .collect();
+        titles.sort();
+        assert_eq!(titles, ["ours", "theirs"]);
+        assert_eq!(saved.bookmarks["url_d"].title(), "d");
+
+        // Test that our edits can still be undone after the merge
+        let mut parsed_file = saved;
+        parsed_file.undo(&mut plain_text).unwrap();
+        let titles: Vec<&str> = parsed_file.all_bookmarks().map(|b| b.title()).collect();
+        assert!(!titles.contains(&"ours"));
+        assert!(!titles.contains(&"d"));
+        assert!(titles.contains(&"theirs"));
+
+        fs::remove_file(plain_text.journal_path()).unwrap();
+        fs::remove_file(&path).unwrap();
+        let _ = fs::remove_file(format!("{}.{}", path.display(), LOCK_FILE_EXTENSION));
+    }
+
+    #[test]
+    fn test_plain_text_write_skips_history_backups() {
+        let directory = std::env::temp_dir().join(format!("fmark_history_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("bookmarks.txt");
+        let original = Bookmark::default().to_line(0, 0);
+        fs::write(&path, &original).unwrap();
+
+        let mut plain_text = PlainText::new(path.clone());
+        plain_text.set_backups(Backups::new(&path, None, 2));
+        plain_text.read().unwrap();
+        let mut parsed_file = ParsedFile::new(plain_text.bookmarks());
+        let other = Bookmark::new("a".to_string(), "b".to_string(), "url_a".to_string());
+        parsed_file.add_bookmark(&mut plain_text, other);
+        plain_text.write(&parsed_file).unwrap();
+        assert_eq!(plain_text.backups().list().unwrap().len(), 1);
+
+        // Test that opening a bookmark many times keeps the backup of the real edit
+        for _ in 0..5 {
+            parsed_file.visit_bookmark(&mut plain_text, &Bookmark::default());
+            plain_text.write(&parsed_file).unwrap();
+        }
+        let backups = plain_text.backups().list().unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].read().unwrap(), original);
+        let saved = ParsedFile::new(&fs::read_to_string(&path).unwrap());
+        assert_eq!(saved.bookmarks[Bookmark::default().url()].visit_count(), 5);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_plain_text_write_atomically() {
+        let path = std::env::temp_dir().join(format!("fmark_atomic_{}.txt", std::process::id()));
+        fs::write(&path, "old contents").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+
+        assert!(PlainText::write_atomically(&path, "new contents").is_ok());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new contents");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // Test that no temporary file is left behind
+        let temp_files = fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.starts_with(&format!(".fmark_atomic_{}", std::process::id()))
+            })
+            .count();
+        assert_eq!(temp_files, 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_plain_text_update_bookmarks() {
         let path = PathBuf::from("test.txt");
@@ -227,6 +630,75 @@ mod tests {
         assert!(!plain_text.bookmarks().is_empty());
     }
 
+    #[test]
+    fn test_plain_text_lines_with_tag() {
+        let path = PathBuf::from("test.txt");
+        let _ = File::create(path.clone()).unwrap();
+        let mut plain_text = PlainText::new(path);
+        let mut parsed_file = ParsedFile::new(plain_text.bookmarks());
+        parsed_file.add_bookmark(
+            &mut plain_text,
+            Bookmark::default().with_tags(vec!["rust".to_string()]),
+        );
+        parsed_file.add_bookmark(
+            &mut plain_text,
+            Bookmark::new(
+                "title".to_string(),
+                "category".to_string(),
+                "url".to_string(),
+            ),
+        );
+        plain_text.update_bookmarks(&parsed_file);
+        let tagged = PlainText::lines_with_tag(plain_text.bookmarks(), "rust");
+        assert_eq!(tagged.lines().count(), 1);
+        assert!(tagged.contains(Bookmark::default().url()));
+        assert!(PlainText::lines_with_tag(plain_text.bookmarks(), "unknown").is_empty());
+    }
+
+    #[test]
+    fn test_plain_text_menu_bookmarks() {
+        let path = PathBuf::from("test.txt");
+        let _ = File::create(path.clone()).unwrap();
+        let mut plain_text = PlainText::new(path);
+        let mut parsed_file = ParsedFile::new(plain_text.bookmarks());
+        let now = Bookmark::timestamp_now();
+        let bookmarks = [
+            Bookmark::new("a".to_string(), "a".to_string(), "never".to_string()),
+            Bookmark::new("b".to_string(), "b".to_string(), "daily".to_string())
+                .with_visits(Some(now), 20),
+            Bookmark::new("c".to_string(), "c".to_string(), "once".to_string())
+                .with_visits(Some(now), 1),
+        ];
+        for bookmark in bookmarks {
+            parsed_file.add_bookmark(&mut plain_text, bookmark);
+        }
+        let urls = |list: String| {
+            list.lines()
+                .filter_map(Bookmark::from_line)
+                .map(|bookmark| bookmark.url().to_string())
+                .collect::<Vec<String>>()
+        };
+
+        // Test that the alphabetic order matches the file, without the history
+        plain_text.update_bookmarks(&parsed_file);
+        let list = plain_text.menu_bookmarks(&parsed_file);
+        assert_eq!(list.lines().count(), plain_text.bookmarks().lines().count());
+        assert!(plain_text.bookmarks().contains("{N}{20}"));
+        assert!(!list.contains("{A}") && !list.contains("{V}") && !list.contains("{N}"));
+        assert_eq!(urls(list), ["never", "daily", "once"]);
+
+        // Test that the frecency order puts the most visited bookmarks first
+        plain_text.set_sort_order(SortOrder::Frecency);
+        let list = plain_text.menu_bookmarks(&parsed_file);
+        assert_eq!(urls(list.clone()), ["daily", "once", "never"]);
+
+        // Test that every menu line has a preview
+        let previews = PlainText::menu_previews(&parsed_file);
+        for line in list.lines() {
+            assert!(previews[line].contains(Bookmark::from_line(line).unwrap().url()));
+        }
+    }
+
     #[test]
     fn test_plain_text_update_categories() {
         let path = PathBuf::from("test.txt");
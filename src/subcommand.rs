@@ -0,0 +1,1347 @@
+use std::collections::HashMap;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+use crate::backup::Backup;
+use crate::bookmark::Bookmark;
+use crate::check::{self, LinkStatus};
+use crate::html;
+use crate::json;
+use crate::parsed_file::{OnDuplicate, ParsedFile, SUPPORTED_ON_DUPLICATE};
+use crate::plain_text::{PlainText, SortOrder};
+use crate::title;
+
+pub const LIST_SUBCOMMAND: &str = "list";
+pub const ADD_SUBCOMMAND: &str = "add";
+pub const REMOVE_SUBCOMMAND: &str = "remove";
+pub const MODIFY_SUBCOMMAND: &str = "modify";
+pub const IMPORT_SUBCOMMAND: &str = "import";
+pub const EXPORT_SUBCOMMAND: &str = "export";
+pub const RESTORE_SUBCOMMAND: &str = "restore";
+pub const UNDO_SUBCOMMAND: &str = "undo";
+pub const REDO_SUBCOMMAND: &str = "redo";
+pub const DEDUPE_SUBCOMMAND: &str = "dedupe";
+pub const CHECK_SUBCOMMAND: &str = "check";
+
+pub const CATEGORY_ARG_LONG: &str = "--category";
+pub const CATEGORY_ARG_SHORT: &str = "-c";
+pub const TITLE_ARG_LONG: &str = "--title";
+pub const TITLE_ARG_SHORT: &str = "-t";
+pub const URL_ARG_LONG: &str = "--url";
+pub const URL_ARG_SHORT: &str = "-u";
+pub const TAG_ARG_LONG: &str = "--tag";
+pub const TAG_ARG_SHORT: &str = "-l";
+pub const TAGS_ARG_LONG: &str = "--tags";
+pub const TAGS_ARG_SHORT: &str = "-g";
+pub const DESCRIPTION_ARG_LONG: &str = "--description";
+pub const DESCRIPTION_ARG_SHORT: &str = "-d";
+pub const FORMAT_ARG_LONG: &str = "--format";
+pub const FORMAT_ARG_SHORT: &str = "-f";
+pub const ON_DUPLICATE_ARG_LONG: &str = "--on-duplicate";
+pub const ON_DUPLICATE_ARG_SHORT: &str = "-o";
+pub const JOBS_ARG_LONG: &str = "--jobs";
+pub const JOBS_ARG_SHORT: &str = "-j";
+pub const TIMEOUT_ARG_LONG: &str = "--timeout";
+pub const TIMEOUT_ARG_SHORT: &str = "-s";
+pub const REDIRECTS_ARG_LONG: &str = "--redirects";
+pub const REDIRECTS_ARG_SHORT: &str = "-r";
+pub const COPY_ARG_LONG: &str = "--copy";
+pub const COPY_ARG_SHORT: &str = "-n";
+
+pub const SUPPORTED_FORMATS: [&str; 2] = ["html", "json"];
+pub const DEFAULT_IMPORT_ON_DUPLICATE: &str = "skip";
+pub const SUPPORTED_REDIRECTS: [&str; 2] = ["report", "rewrite"];
+pub const DEFAULT_CHECK_JOBS: usize = 8;
+pub const DEFAULT_CHECK_TIMEOUT: u64 = 10;
+
+pub enum Format {
+    Html,
+    Json,
+}
+
+impl Format {
+    pub fn new(format: Option<String>) -> Result<Self, String> {
+        match format.as_deref() {
+            Some("html") => Ok(Self::Html),
+            Some("json") => Ok(Self::Json),
+            Some(format) => Err(format!("Unsupported format: {}", format)),
+            None => Err(format!(
+                "Error: Missing '{}'. Supported formats are '{}'.",
+                FORMAT_ARG_LONG,
+                SUPPORTED_FORMATS.join("', '")
+            )),
+        }
+    }
+}
+
+pub enum Subcommand {
+    List {
+        category: Option<String>,
+        title: Option<String>,
+        url: Option<String>,
+        tag: Option<String>,
+    },
+    Add {
+        title: Option<String>,
+        category: String,
+        url: String,
+        tags: Vec<String>,
+        description: String,
+        on_duplicate: Option<OnDuplicate>,
+    },
+    Remove {
+        url: String,
+        copy: Option<usize>,
+    },
+    Modify {
+        url: String,
+        copy: Option<usize>,
+        title: Option<String>,
+        category: Option<String>,
+        new_url: Option<String>,
+        tags: Option<Vec<String>>,
+        description: Option<String>,
+    },
+    Import {
+        format: Format,
+        file_path: PathBuf,
+        on_duplicate: OnDuplicate,
+    },
+    Export {
+        format: Format,
+    },
+    Restore {
+        number: Option<usize>,
+    },
+    Undo,
+    Redo,
+    Dedupe,
+    Check {
+        jobs: usize,
+        timeout: u64,
+        rewrite: bool,
+    },
+}
+
+impl Subcommand {
+    pub fn new(args: Vec<String>) -> Result<Self, String> {
+        let (name, args) = match args.split_first() {
+            Some((name, args)) => (name.as_str(), args),
+            None => return Err("Missing subcommand.".to_string()),
+        };
+
+        match name {
+            LIST_SUBCOMMAND => {
+                let (_, mut options) = Self::get_options(
+                    name,
+                    args,
+                    &[
+                        (CATEGORY_ARG_LONG, CATEGORY_ARG_SHORT),
+                        (TITLE_ARG_LONG, TITLE_ARG_SHORT),
+                        (URL_ARG_LONG, URL_ARG_SHORT),
+                        (TAG_ARG_LONG, TAG_ARG_SHORT),
+                    ],
+                    0..=0,
+                )?;
+                Ok(Self::List {
+                    category: options.remove(CATEGORY_ARG_LONG),
+                    title: options.remove(TITLE_ARG_LONG),
+                    url: options.remove(URL_ARG_LONG),
+                    tag: options.remove(TAG_ARG_LONG),
+                })
+            }
+            ADD_SUBCOMMAND => {
+                let (_, mut options) = Self::get_options(
+                    name,
+                    args,
+                    &[
+                        (TITLE_ARG_LONG, TITLE_ARG_SHORT),
+                        (CATEGORY_ARG_LONG, CATEGORY_ARG_SHORT),
+                        (URL_ARG_LONG, URL_ARG_SHORT),
+                        (TAGS_ARG_LONG, TAGS_ARG_SHORT),
+                        (DESCRIPTION_ARG_LONG, DESCRIPTION_ARG_SHORT),
+                        (ON_DUPLICATE_ARG_LONG, ON_DUPLICATE_ARG_SHORT),
+                    ],
+                    0..=0,
+                )?;
+                Ok(Self::Add {
+                    title: Self::optional_option(name, &mut options, TITLE_ARG_LONG)?,
+                    category: Self::required_option(name, &mut options, CATEGORY_ARG_LONG)?,
+                    url: Self::required_option(name, &mut options, URL_ARG_LONG)?,
+                    tags: Bookmark::parse_tags(&options.remove(TAGS_ARG_LONG).unwrap_or_default()),
+                    description: options.remove(DESCRIPTION_ARG_LONG).unwrap_or_default(),
+                    on_duplicate: options
+                        .remove(ON_DUPLICATE_ARG_LONG)
+                        .map(|on_duplicate| OnDuplicate::new(&on_duplicate))
+                        .transpose()?,
+                })
+            }
+            REMOVE_SUBCOMMAND => {
+                let (mut positional, mut options) = Self::get_options(
+                    name,
+                    args,
+                    &[(COPY_ARG_LONG, COPY_ARG_SHORT)],
+                    1..=1,
+                )?;
+                Ok(Self::Remove {
+                    url: positional.remove(0),
+                    copy: Self::copy_option(&mut options)?,
+                })
+            }
+            MODIFY_SUBCOMMAND => {
+                let (mut positional, mut options) = Self::get_options(
+                    name,
+                    args,
+                    &[
+                        (TITLE_ARG_LONG, TITLE_ARG_SHORT),
+                        (CATEGORY_ARG_LONG, CATEGORY_ARG_SHORT),
+                        (URL_ARG_LONG, URL_ARG_SHORT),
+                        (TAGS_ARG_LONG, TAGS_ARG_SHORT),
+                        (DESCRIPTION_ARG_LONG, DESCRIPTION_ARG_SHORT),
+                        (COPY_ARG_LONG, COPY_ARG_SHORT),
+                    ],
+                    1..=1,
+                )?;
+                Ok(Self::Modify {
+                    url: positional.remove(0),
+                    copy: Self::copy_option(&mut options)?,
+                    title: Self::optional_option(name, &mut options, TITLE_ARG_LONG)?,
+                    category: Self::optional_option(name, &mut options, CATEGORY_ARG_LONG)?,
+                    new_url: Self::optional_option(name, &mut options, URL_ARG_LONG)?,
+                    tags: options
+                        .remove(TAGS_ARG_LONG)
+                        .map(|tags| Bookmark::parse_tags(&tags)),
+                    description: options.remove(DESCRIPTION_ARG_LONG),
+                })
+            }
+            IMPORT_SUBCOMMAND => {
+                let (mut positional, mut options) = Self::get_options(
+                    name,
+                    args,
+                    &[
+                        (FORMAT_ARG_LONG, FORMAT_ARG_SHORT),
+                        (ON_DUPLICATE_ARG_LONG, ON_DUPLICATE_ARG_SHORT),
+                    ],
+                    1..=1,
+                )?;
+                Ok(Self::Import {
+                    format: Format::new(options.remove(FORMAT_ARG_LONG))?,
+                    file_path: PathBuf::from(positional.remove(0)),
+                    on_duplicate: OnDuplicate::new(
+                        &options
+                            .remove(ON_DUPLICATE_ARG_LONG)
+                            .unwrap_or_else(|| DEFAULT_IMPORT_ON_DUPLICATE.to_string()),
+                    )?,
+                })
+            }
+            EXPORT_SUBCOMMAND => {
+                let (_, mut options) = Self::get_options(
+                    name,
+                    args,
+                    &[(FORMAT_ARG_LONG, FORMAT_ARG_SHORT)],
+                    0..=0,
+                )?;
+                Ok(Self::Export {
+                    format: Format::new(options.remove(FORMAT_ARG_LONG))?,
+                })
+            }
+            RESTORE_SUBCOMMAND => {
+                let (positional, _) = Self::get_options(name, args, &[], 0..=1)?;
+                let number = match positional.first() {
+                    Some(number) => match number.parse::<usize>() {
+                        Ok(number) if number > 0 => Some(number),
+                        _ => return Err(format!("Invalid backup number: {}", number)),
+                    },
+                    None => None,
+                };
+                Ok(Self::Restore { number })
+            }
+            UNDO_SUBCOMMAND => {
+                Self::get_options(name, args, &[], 0..=0)?;
+                Ok(Self::Undo)
+            }
+            REDO_SUBCOMMAND => {
+                Self::get_options(name, args, &[], 0..=0)?;
+                Ok(Self::Redo)
+            }
+            DEDUPE_SUBCOMMAND => {
+                Self::get_options(name, args, &[], 0..=0)?;
+                Ok(Self::Dedupe)
+            }
+            CHECK_SUBCOMMAND => {
+                let (_, mut options) = Self::get_options(
+                    name,
+                    args,
+                    &[
+                        (JOBS_ARG_LONG, JOBS_ARG_SHORT),
+                        (TIMEOUT_ARG_LONG, TIMEOUT_ARG_SHORT),
+                        (REDIRECTS_ARG_LONG, REDIRECTS_ARG_SHORT),
+                    ],
+                    0..=0,
+                )?;
+                let jobs = match options.remove(JOBS_ARG_LONG) {
+                    Some(jobs) => match jobs.parse::<usize>() {
+                        Ok(jobs) if jobs > 0 => jobs,
+                        _ => return Err(format!("Invalid number of jobs: {}", jobs)),
+                    },
+                    None => DEFAULT_CHECK_JOBS,
+                };
+                let timeout = match options.remove(TIMEOUT_ARG_LONG) {
+                    Some(timeout) => match timeout.parse::<u64>() {
+                        Ok(timeout) if timeout > 0 => timeout,
+                        _ => return Err(format!("Invalid timeout: {}", timeout)),
+                    },
+                    None => DEFAULT_CHECK_TIMEOUT,
+                };
+                let rewrite = match options.remove(REDIRECTS_ARG_LONG).as_deref() {
+                    Some("rewrite") => true,
+                    Some("report") | None => false,
+                    Some(redirects) => {
+                        return Err(format!(
+                            "Unsupported redirects option: {}. Supported are '{}'.",
+                            redirects,
+                            SUPPORTED_REDIRECTS.join("', '")
+                        ))
+                    }
+                };
+                Ok(Self::Check {
+                    jobs,
+                    timeout,
+                    rewrite,
+                })
+            }
+            _ => Err(format!(
+                "Error: Unrecognized subcommand '{}'. Use '-h, --help' for more information about available subcommands.",
+                name
+            )),
+        }
+    }
+
+    pub fn run(
+        self,
+        plain_text: &mut PlainText,
+        parsed_file: &mut ParsedFile,
+    ) -> Result<(), String> {
+        match self {
+            Self::List {
+                category,
+                title,
+                url,
+                tag,
+            } => {
+                print!(
+                    "{}",
+                    Self::list(
+                        parsed_file,
+                        category.as_deref(),
+                        title.as_deref(),
+                        url.as_deref(),
+                        tag.as_deref(),
+                        plain_text.sort_order()
+                    )
+                );
+            }
+            Self::Add {
+                title,
+                category,
+                url,
+                tags,
+                description,
+                on_duplicate,
+            } => {
+                let on_duplicate = match (on_duplicate, parsed_file.find_duplicate(&url)) {
+                    (Some(on_duplicate), _) => on_duplicate,
+                    (None, Some(existing)) => {
+                        return Err(format!(
+                            "Bookmark already exists: {}. Use '{}' with '{}' to choose what to do.",
+                            existing.url(),
+                            ON_DUPLICATE_ARG_LONG,
+                            SUPPORTED_ON_DUPLICATE.join("', '")
+                        ))
+                    }
+                    (None, None) => OnDuplicate::KeepBoth,
+                };
+                let title = match title {
+                    Some(title) => title,
+                    None => title::fetch(&url).map_err(|error| {
+                        format!("{}. Use '{}' to set the title.", error, TITLE_ARG_LONG)
+                    })?,
+                };
+                let bookmark = Bookmark::new(title, category, url)
+                    .with_tags(tags)
+                    .with_description(&description);
+                parsed_file.add_bookmark_or_resolve(plain_text, bookmark, &on_duplicate);
+            }
+            Self::Remove { url, copy } => {
+                let bookmark = Self::find_bookmark(parsed_file, &url, copy)?;
+                parsed_file.remove_bookmark(plain_text, &bookmark);
+            }
+            Self::Modify {
+                url,
+                copy,
+                title,
+                category,
+                new_url,
+                tags,
+                description,
+            } => {
+                let old_bookmark = Self::find_bookmark(parsed_file, &url, copy)?;
+                let new_bookmark = Bookmark::new(
+                    title.unwrap_or_else(|| old_bookmark.title().to_string()),
+                    category.unwrap_or_else(|| old_bookmark.category().to_string()),
+                    new_url.unwrap_or(url),
+                )
+                .with_tags(tags.unwrap_or_else(|| old_bookmark.tags().to_vec()))
+                .with_description(
+                    description
+                        .as_deref()
+                        .unwrap_or_else(|| old_bookmark.description()),
+                )
+                .with_history_of(&old_bookmark);
+                parsed_file.modify_bookmark(plain_text, new_bookmark, &old_bookmark);
+            }
+            Self::Import {
+                format,
+                file_path,
+                on_duplicate,
+            } => {
+                let contents = fs::read_to_string(&file_path).map_err(|error| {
+                    format!(
+                        "Failed to read import file {}: {}",
+                        file_path.display(),
+                        error
+                    )
+                })?;
+                let bookmarks = match format {
+                    Format::Html => html::parse(&contents),
+                    Format::Json => json::parse(&contents).map_err(|error| {
+                        format!("Failed to import {}: {}", file_path.display(), error)
+                    })?,
+                };
+                let count = bookmarks.len();
+                let mut skipped = 0;
+                for bookmark in bookmarks {
+                    if !parsed_file.add_bookmark_or_resolve(plain_text, bookmark, &on_duplicate) {
+                        skipped += 1;
+                    }
+                }
+                println!(
+                    "Imported {} bookmarks from {}",
+                    count - skipped,
+                    file_path.display()
+                );
+                if skipped > 0 {
+                    println!(
+                        "Skipped {} bookmarks that were already in the file",
+                        skipped
+                    );
+                }
+            }
+            Self::Export { format } => {
+                let mut bookmarks_vec: Vec<&Bookmark> = parsed_file.all_bookmarks().collect();
+                PlainText::sort_bookmarks(&mut bookmarks_vec);
+                match format {
+                    Format::Html => print!("{}", html::render(&bookmarks_vec)),
+                    Format::Json => print!("{}", json::render(&bookmarks_vec)),
+                }
+            }
+            Self::Restore { number } => {
+                let backups = plain_text.backups().list().map_err(|error| {
+                    format!(
+                        "Failed to list backups in {}: {}",
+                        plain_text.backups().directory().display(),
+                        error
+                    )
+                })?;
+                match number {
+                    Some(number) => {
+                        let backup = backups
+                            .get(number - 1)
+                            .ok_or_else(|| format!("Backup not found: {}", number))?;
+                        *parsed_file = ParsedFile::new(&backup.read()?);
+                        // The recorded edits no longer apply to the restored bookmarks
+                        plain_text.journal_mut().clear();
+                        plain_text.increment_bookmarks_version();
+                        plain_text.increment_categories_version();
+                        plain_text.set_edited_true();
+                        println!(
+                            "Restored backup from {}, undo and redo history was cleared",
+                            backup.date()
+                        );
+                    }
+                    None if backups.is_empty() => println!(
+                        "No backups in {}",
+                        plain_text.backups().directory().display()
+                    ),
+                    None => print!("{}", Self::backup_list(&backups)),
+                }
+            }
+            Self::Undo => {
+                parsed_file.undo(plain_text)?;
+                println!("Undid the last change");
+            }
+            Self::Redo => {
+                parsed_file.redo(plain_text)?;
+                println!("Redid the last undone change");
+            }
+            Self::Dedupe => print!("{}", Self::dedupe(parsed_file)),
+            Self::Check {
+                jobs,
+                timeout,
+                rewrite,
+            } => {
+                let mut bookmarks: Vec<Bookmark> = parsed_file.all_bookmarks().cloned().collect();
+                bookmarks.sort_by(|a, b| a.url().cmp(b.url()));
+                let urls: Vec<&str> = bookmarks.iter().map(|bookmark| bookmark.url()).collect();
+                let results = check::check_urls(&urls, jobs, timeout);
+                print!("{}", Self::check_report(&bookmarks, &results));
+                if rewrite {
+                    print!(
+                        "{}",
+                        Self::rewrite_redirects(plain_text, parsed_file, &bookmarks, &results)
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn list(
+        parsed_file: &ParsedFile,
+        category: Option<&str>,
+        title: Option<&str>,
+        url: Option<&str>,
+        tag: Option<&str>,
+        sort_order: &SortOrder,
+    ) -> String {
+        let mut bookmarks_vec: Vec<&Bookmark> = parsed_file
+            .all_bookmarks()
+            .filter(|bookmark| {
+                category.is_none_or(|category| {
+                    bookmark.category().to_lowercase() == category.to_lowercase()
+                }) && title.is_none_or(|title| Self::contains_ignore_case(bookmark.title(), title))
+                    && url.is_none_or(|url| Self::contains_ignore_case(bookmark.url(), url))
+                    && tag.is_none_or(|tag| {
+                        bookmark
+                            .tags()
+                            .iter()
+                            .any(|bookmark_tag| bookmark_tag.to_lowercase() == tag.to_lowercase())
+                    })
+            })
+            .collect();
+        PlainText::sort_bookmarks_by(&mut bookmarks_vec, sort_order);
+
+        let longest_title = bookmarks_vec
+            .iter()
+            .map(|bookmark| bookmark.title().chars().count())
+            .max()
+            .unwrap_or(0);
+        let longest_category = bookmarks_vec
+            .iter()
+            .map(|bookmark| bookmark.category().chars().count())
+            .max()
+            .unwrap_or(0);
+
+        bookmarks_vec
+            .iter()
+            .map(|bookmark| bookmark.to_line(longest_title, longest_category))
+            .collect()
+    }
+
+    fn dedupe(parsed_file: &ParsedFile) -> String {
+        let groups = parsed_file.duplicate_groups();
+        if groups.is_empty() {
+            return "No duplicate bookmarks found\n".to_string();
+        }
+
+        let mut report = String::new();
+        for (url, bookmarks) in groups {
+            let longest_title = bookmarks
+                .iter()
+                .map(|bookmark| bookmark.title().chars().count())
+                .max()
+                .unwrap_or(0);
+            let longest_category = bookmarks
+                .iter()
+                .map(|bookmark| bookmark.category().chars().count())
+                .max()
+                .unwrap_or(0);
+            report.push_str(&format!("{} bookmarks for {}\n", bookmarks.len(), url));
+            for bookmark in bookmarks {
+                report.push_str(&format!(
+                    "  {}",
+                    bookmark.to_line(longest_title, longest_category)
+                ));
+            }
+        }
+        report
+    }
+
+    fn check_report(bookmarks: &[Bookmark], results: &[LinkStatus]) -> String {
+        let mut report = String::new();
+        let (mut ok, mut broken, mut redirected, mut skipped) = (0, 0, 0, 0);
+        for (bookmark, status) in bookmarks.iter().zip(results) {
+            match status {
+                LinkStatus::Ok => ok += 1,
+                LinkStatus::Skipped => skipped += 1,
+                LinkStatus::Redirected { .. } => redirected += 1,
+                LinkStatus::Broken { .. } | LinkStatus::Failed { .. } => broken += 1,
+            }
+            if !matches!(status, LinkStatus::Ok | LinkStatus::Skipped) {
+                report.push_str(&format!("{}: {}\n", bookmark.url(), status.describe()));
+            }
+        }
+        report.push_str(&format!(
+            "Checked {} bookmarks: {} ok, {} broken, {} moved permanently, {} skipped\n",
+            results.len(),
+            ok,
+            broken,
+            redirected,
+            skipped
+        ));
+        report
+    }
+
+    fn rewrite_redirects(
+        plain_text: &mut PlainText,
+        parsed_file: &mut ParsedFile,
+        bookmarks: &[Bookmark],
+        results: &[LinkStatus],
+    ) -> String {
+        let mut report = String::new();
+        let mut rewritten = 0;
+        for (bookmark, status) in bookmarks.iter().zip(results) {
+            let LinkStatus::Redirected { location } = status else {
+                continue;
+            };
+            // A redirect that only normalizes the url matches the bookmark being rewritten
+            if parsed_file
+                .find_duplicate(location)
+                .filter(|duplicate| duplicate.url() != bookmark.url())
+                .is_some()
+            {
+                report.push_str(&format!(
+                    "Not rewriting {}: {} is already bookmarked\n",
+                    bookmark.url(),
+                    location
+                ));
+                continue;
+            }
+            let new_bookmark = Bookmark::new(
+                bookmark.title().to_string(),
+                bookmark.category().to_string(),
+                location.clone(),
+            )
+            .with_tags(bookmark.tags().to_vec())
+            .with_description(bookmark.description())
+            .with_history_of(bookmark);
+            parsed_file.modify_bookmark(plain_text, new_bookmark, bookmark);
+            rewritten += 1;
+        }
+        report.push_str(&format!("Rewrote {} moved URLs\n", rewritten));
+        report
+    }
+
+    fn backup_list(backups: &[Backup]) -> String {
+        backups
+            .iter()
+            .enumerate()
+            .map(|(i, backup)| {
+                format!(
+                    "{:>3}  {}  {}\n",
+                    i + 1,
+                    backup.date(),
+                    backup.path().display()
+                )
+            })
+            .collect()
+    }
+
+    fn contains_ignore_case(field: &str, pattern: &str) -> bool {
+        field.to_lowercase().contains(&pattern.to_lowercase())
+    }
+
+    fn find_bookmark(
+        parsed_file: &ParsedFile,
+        url: &str,
+        copy: Option<usize>,
+    ) -> Result<Bookmark, String> {
+        let mut copies = parsed_file.copies(url);
+        match (copies.len(), copy) {
+            (0, _) => Err(format!("Bookmark not found: {}", url)),
+            (1, None) => Ok(copies.remove(0)),
+            (count, None) => {
+                let mut message = format!(
+                    "Bookmark is ambiguous, {} copies of {}. Use '{}' to choose one:",
+                    count, url, COPY_ARG_LONG
+                );
+                for (number, bookmark) in copies.iter().enumerate() {
+                    message.push_str(&format!(
+                        "\n  {}: {} {}",
+                        number + 1,
+                        bookmark.title(),
+                        bookmark.category()
+                    ));
+                }
+                Err(message)
+            }
+            (count, Some(number)) if number > count => Err(format!(
+                "Invalid copy number: {}. There are {} copies of {}.",
+                number, count, url
+            )),
+            (_, Some(number)) => Ok(copies.remove(number - 1)),
+        }
+    }
+
+    fn copy_option(options: &mut HashMap<&'static str, String>) -> Result<Option<usize>, String> {
+        match options.remove(COPY_ARG_LONG) {
+            Some(copy) => match copy.parse::<usize>() {
+                Ok(copy) if copy > 0 => Ok(Some(copy)),
+                _ => Err(format!("Invalid copy number: {}", copy)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn optional_option(
+        name: &str,
+        options: &mut HashMap<&'static str, String>,
+        option: &str,
+    ) -> Result<Option<String>, String> {
+        match options.contains_key(option) {
+            true => Self::required_option(name, options, option).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    fn required_option(
+        name: &str,
+        options: &mut HashMap<&'static str, String>,
+        option: &str,
+    ) -> Result<String, String> {
+        match options.remove(option) {
+            Some(value) if !value.trim().is_empty() => Ok(value.trim().to_string()),
+            _ => Err(format!("Error: '{}' requires '{}'.", name, option)),
+        }
+    }
+
+    fn get_options(
+        name: &str,
+        args: &[String],
+        known_options: &[(&'static str, &'static str)],
+        positional_count: RangeInclusive<usize>,
+    ) -> Result<(Vec<String>, HashMap<&'static str, String>), String> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                positional.push(arg.clone());
+                continue;
+            }
+            let (long, _) = known_options
+                .iter()
+                .find(|(long, short)| arg == long || arg == short)
+                .ok_or_else(|| {
+                    format!(
+                        "Error: Unrecognized argument '{}' for '{}'. Use '-h, --help' for more information about available options.",
+                        arg, name
+                    )
+                })?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Error: Missing value for '{}'.", arg))?;
+            options.insert(*long, value.clone());
+        }
+
+        if !positional_count.contains(&positional.len()) {
+            let expected = match positional_count.start() == positional_count.end() {
+                true => positional_count.start().to_string(),
+                false => format!("{} to {}", positional_count.start(), positional_count.end()),
+            };
+            return Err(format!(
+                "Error: '{}' expects {} argument(s), got {}.",
+                name,
+                expected,
+                positional.len()
+            ));
+        }
+
+        Ok((positional, options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::backup::Backups;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn parsed_file() -> ParsedFile {
+        let bookmarks = [
+            Bookmark::new(
+                "Rust Programming".to_string(),
+                "Programming".to_string(),
+                "https://www.rust-lang.org/".to_string(),
+            ),
+            Bookmark::new(
+                "Crates".to_string(),
+                "Programming".to_string(),
+                "https://crates.io/".to_string(),
+            ),
+            Bookmark::new(
+                "News".to_string(),
+                "Reading".to_string(),
+                "https://news.ycombinator.com/".to_string(),
+            )
+            .with_tags(vec!["daily".to_string()]),
+        ];
+        let file = bookmarks
+            .iter()
+            .map(|bookmark| bookmark.to_line(20, 20))
+            .collect::<String>();
+        ParsedFile::new(&file)
+    }
+
+    #[test]
+    fn test_subcommand_new() {
+        let subcommand = Subcommand::new(args(&["list", "--category", "Reading", "-t", "news"]));
+        match subcommand {
+            Ok(Subcommand::List {
+                category,
+                title,
+                url,
+                tag,
+            }) => {
+                assert_eq!(category.as_deref(), Some("Reading"));
+                assert_eq!(title.as_deref(), Some("news"));
+                assert!(url.is_none());
+                assert!(tag.is_none());
+            }
+            _ => panic!("expected list subcommand"),
+        }
+
+        let subcommand = Subcommand::new(args(&[
+            "add",
+            "--title",
+            " Crates ",
+            "-c",
+            "Programming",
+            "-u",
+            "https://crates.io/",
+            "--tags",
+            "rust, packages",
+        ]));
+        match subcommand {
+            Ok(Subcommand::Add {
+                title,
+                category,
+                url,
+                tags,
+                description,
+                on_duplicate,
+            }) => {
+                assert!(description.is_empty());
+                assert!(on_duplicate.is_none());
+                assert_eq!(title.as_deref(), Some("Crates"));
+                assert_eq!(category, "Programming");
+                assert_eq!(url, "https://crates.io/");
+                assert_eq!(tags, ["rust", "packages"]);
+            }
+            _ => panic!("expected add subcommand"),
+        }
+
+        // Test with a missing or empty required option
+        assert!(Subcommand::new(args(&["add", "-t", "Crates", "-c", "Programming"])).is_err());
+        assert!(
+            Subcommand::new(args(&["add", "-t", " ", "-c", "Programming", "-u", "url"])).is_err()
+        );
+        // Test that the title is optional, it is fetched from the page when missing
+        assert!(matches!(
+            Subcommand::new(args(&["add", "-c", "Programming", "-u", "url"])),
+            Ok(Subcommand::Add { title: None, .. })
+        ));
+
+        let subcommand = Subcommand::new(args(&[
+            "modify",
+            "https://crates.io/",
+            "-t",
+            "Crates",
+            "-d",
+            "Package registry",
+        ]));
+        match subcommand {
+            Ok(Subcommand::Modify {
+                url,
+                copy,
+                title,
+                category,
+                new_url,
+                tags,
+                description,
+            }) => {
+                assert_eq!(description.as_deref(), Some("Package registry"));
+                assert_eq!(url, "https://crates.io/");
+                assert!(copy.is_none());
+                assert_eq!(title.as_deref(), Some("Crates"));
+                assert!(category.is_none());
+                assert!(new_url.is_none());
+                assert!(tags.is_none());
+            }
+            _ => panic!("expected modify subcommand"),
+        }
+
+        let subcommand = Subcommand::new(args(&["remove", "https://crates.io/", "-n", "2"]));
+        match subcommand {
+            Ok(Subcommand::Remove { url, copy }) => {
+                assert_eq!(url, "https://crates.io/");
+                assert_eq!(copy, Some(2));
+            }
+            _ => panic!("expected remove subcommand"),
+        }
+        assert!(Subcommand::new(args(&["remove", "https://crates.io/", "--copy", "0"])).is_err());
+
+        let subcommand = Subcommand::new(args(&["import", "-f", "html", "bookmarks.html"]));
+        match subcommand {
+            Ok(Subcommand::Import {
+                format: Format::Html,
+                file_path,
+                on_duplicate: OnDuplicate::Skip,
+            }) => assert_eq!(file_path, PathBuf::from("bookmarks.html")),
+            _ => panic!("expected import subcommand"),
+        }
+
+        let subcommand = Subcommand::new(args(&["export", "--format", "html"]));
+        assert!(matches!(
+            subcommand,
+            Ok(Subcommand::Export {
+                format: Format::Html
+            })
+        ));
+
+        let subcommand = Subcommand::new(args(&["import", "-f", "json", "bookmarks.json"]));
+        assert!(matches!(
+            subcommand,
+            Ok(Subcommand::Import {
+                format: Format::Json,
+                ..
+            })
+        ));
+
+        // Test with a missing or unsupported format
+        assert!(Subcommand::new(args(&["export"])).is_err());
+        assert!(Subcommand::new(args(&["import", "bookmarks.html"])).is_err());
+        assert!(Subcommand::new(args(&["import", "-f", "xml", "bookmarks.html"])).is_err());
+
+        let subcommand = Subcommand::new(args(&["restore"]));
+        assert!(matches!(
+            subcommand,
+            Ok(Subcommand::Restore { number: None })
+        ));
+        let subcommand = Subcommand::new(args(&["restore", "2"]));
+        assert!(matches!(
+            subcommand,
+            Ok(Subcommand::Restore { number: Some(2) })
+        ));
+
+        assert!(matches!(
+            Subcommand::new(args(&["undo"])),
+            Ok(Subcommand::Undo)
+        ));
+        assert!(matches!(
+            Subcommand::new(args(&["redo"])),
+            Ok(Subcommand::Redo)
+        ));
+        assert!(Subcommand::new(args(&["undo", "2"])).is_err());
+        assert!(matches!(
+            Subcommand::new(args(&["dedupe"])),
+            Ok(Subcommand::Dedupe)
+        ));
+
+        // Test with an invalid backup number
+        assert!(Subcommand::new(args(&["restore", "0"])).is_err());
+        assert!(Subcommand::new(args(&["restore", "1", "2"])).is_err());
+
+        // Test with a missing url
+        assert!(Subcommand::new(args(&["remove"])).is_err());
+        assert!(Subcommand::new(args(&["modify", "-t", "Crates"])).is_err());
+
+        // Test with an unknown subcommand
+        assert!(Subcommand::new(args(&["unknown"])).is_err());
+
+        // Test with an unknown option and a missing option value
+        assert!(Subcommand::new(args(&["list", "--unknown", "value"])).is_err());
+        assert!(Subcommand::new(args(&["list", "--url"])).is_err());
+
+        // Test with an unexpected positional argument
+        assert!(Subcommand::new(args(&["list", "extra"])).is_err());
+    }
+
+    #[test]
+    fn test_subcommand_list() {
+        let parsed_file = parsed_file();
+
+        // Test without filters, should list every bookmark sorted by category then title
+        let list = Subcommand::list(&parsed_file, None, None, None, None, &SortOrder::Alphabetic);
+        let titles: Vec<String> = list
+            .lines()
+            .filter_map(Bookmark::from_line)
+            .map(|bookmark| bookmark.title().to_string())
+            .collect();
+        assert_eq!(titles, ["Crates", "Rust Programming", "News"]);
+
+        // Test with the frecency order, unvisited bookmarks keep the alphabetic order
+        let frecency_list =
+            Subcommand::list(&parsed_file, None, None, None, None, &SortOrder::Frecency);
+        assert_eq!(frecency_list, list);
+
+        // Test with a category filter
+        let list = Subcommand::list(
+            &parsed_file,
+            Some("programming"),
+            None,
+            None,
+            None,
+            &SortOrder::Alphabetic,
+        );
+        assert_eq!(list.lines().count(), 2);
+
+        // Test with a title and url filter
+        let list = Subcommand::list(
+            &parsed_file,
+            None,
+            Some("rust"),
+            None,
+            None,
+            &SortOrder::Alphabetic,
+        );
+        assert_eq!(list.lines().count(), 1);
+        let list = Subcommand::list(
+            &parsed_file,
+            None,
+            None,
+            Some("CRATES.IO"),
+            None,
+            &SortOrder::Alphabetic,
+        );
+        assert_eq!(list.lines().count(), 1);
+
+        // Test with a tag filter
+        let list = Subcommand::list(
+            &parsed_file,
+            None,
+            None,
+            None,
+            Some("Daily"),
+            &SortOrder::Alphabetic,
+        );
+        assert_eq!(list.lines().count(), 1);
+        assert!(list.contains("{G}{daily}"));
+
+        // Test with filters that match nothing
+        let list = Subcommand::list(
+            &parsed_file,
+            Some("Reading"),
+            Some("rust"),
+            None,
+            None,
+            &SortOrder::Alphabetic,
+        );
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_subcommand_run_add() {
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let mut parsed_file = parsed_file();
+        let subcommand = Subcommand::Add {
+            title: Some("Docs".to_string()),
+            category: "Reference".to_string(),
+            url: "https://docs.rs/".to_string(),
+            tags: vec!["rust".to_string()],
+            description: "Documentation for crates".to_string(),
+            on_duplicate: None,
+        };
+        assert!(subcommand.run(&mut plain_text, &mut parsed_file).is_ok());
+        assert_eq!(parsed_file.bookmarks.len(), 4);
+        assert_eq!(parsed_file.categories().len(), 3);
+        assert_eq!(parsed_file.tags().len(), 2);
+        assert_eq!(
+            parsed_file.bookmarks["https://docs.rs/"].description(),
+            "Documentation for crates"
+        );
+        assert!(plain_text.edited());
+
+        // Test with a url that is already in the file
+        let duplicate = |on_duplicate| Subcommand::Add {
+            title: Some("Crates.io".to_string()),
+            category: "Rust".to_string(),
+            url: "https://crates.io/".to_string(),
+            tags: vec!["rust".to_string()],
+            description: String::new(),
+            on_duplicate,
+        };
+        assert!(duplicate(None)
+            .run(&mut plain_text, &mut parsed_file)
+            .is_err());
+        assert!(duplicate(Some(OnDuplicate::Skip))
+            .run(&mut plain_text, &mut parsed_file)
+            .is_ok());
+        assert_eq!(
+            parsed_file.bookmarks["https://crates.io/"].title(),
+            "Crates"
+        );
+        assert!(duplicate(Some(OnDuplicate::Merge))
+            .run(&mut plain_text, &mut parsed_file)
+            .is_ok());
+        let bookmark = &parsed_file.bookmarks["https://crates.io/"];
+        assert_eq!(bookmark.title(), "Crates");
+        assert_eq!(bookmark.tags(), ["rust"]);
+        assert!(duplicate(Some(OnDuplicate::KeepBoth))
+            .run(&mut plain_text, &mut parsed_file)
+            .is_ok());
+        assert_eq!(parsed_file.duplicates().len(), 1);
+        assert_eq!(parsed_file.duplicates()[0].title(), "Crates.io");
+    }
+
+    #[test]
+    fn test_subcommand_run_remove() {
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let mut parsed_file = parsed_file();
+        let subcommand = Subcommand::Remove {
+            url: "https://news.ycombinator.com/".to_string(),
+            copy: None,
+        };
+        assert!(subcommand.run(&mut plain_text, &mut parsed_file).is_ok());
+        assert_eq!(parsed_file.bookmarks.len(), 2);
+        assert_eq!(parsed_file.categories().len(), 1);
+        assert!(plain_text.edited());
+
+        // Test with a url that is not in the file
+        let subcommand = Subcommand::Remove {
+            url: "https://news.ycombinator.com/".to_string(),
+            copy: None,
+        };
+        assert!(subcommand.run(&mut plain_text, &mut parsed_file).is_err());
+
+        // Test that copies of a url have to be chosen by number
+        let copy = Bookmark::new(
+            "Crates copy".to_string(),
+            "Programming".to_string(),
+            "https://crates.io/".to_string(),
+        );
+        parsed_file.add_bookmark(&mut plain_text, copy);
+        let remove = |copy| Subcommand::Remove {
+            url: "https://crates.io/".to_string(),
+            copy,
+        };
+        let error = remove(None)
+            .run(&mut plain_text, &mut parsed_file)
+            .err()
+            .unwrap();
+        assert!(error.contains("ambiguous, 2 copies"));
+        assert!(error.contains("1: Crates copy Programming"));
+        assert!(error.contains("2: Crates Programming"));
+        assert!(remove(Some(3))
+            .run(&mut plain_text, &mut parsed_file)
+            .is_err());
+        assert!(remove(Some(1))
+            .run(&mut plain_text, &mut parsed_file)
+            .is_ok());
+        assert!(parsed_file.duplicates().is_empty());
+        assert_eq!(
+            parsed_file.bookmarks["https://crates.io/"].title(),
+            "Crates"
+        );
+    }
+
+    #[test]
+    fn test_subcommand_run_modify() {
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let mut parsed_file = parsed_file();
+        let subcommand = Subcommand::Modify {
+            url: "https://crates.io/".to_string(),
+            copy: None,
+            title: None,
+            category: Some("Rust".to_string()),
+            new_url: Some("https://lib.rs/".to_string()),
+            tags: None,
+            description: None,
+        };
+        assert!(subcommand.run(&mut plain_text, &mut parsed_file).is_ok());
+        assert!(!parsed_file.bookmarks.contains_key("https://crates.io/"));
+        let bookmark = &parsed_file.bookmarks["https://lib.rs/"];
+        assert_eq!(bookmark.title(), "Crates");
+        assert_eq!(bookmark.category(), "Rust");
+        assert!(plain_text.edited());
+
+        // Test with a url that is not in the file
+        let subcommand = Subcommand::Modify {
+            url: "https://crates.io/".to_string(),
+            copy: None,
+            title: Some("Crates".to_string()),
+            category: None,
+            new_url: None,
+            tags: Some(Vec::new()),
+            description: Some(String::new()),
+        };
+        assert!(subcommand.run(&mut plain_text, &mut parsed_file).is_err());
+    }
+
+    #[test]
+    fn test_subcommand_run_restore() {
+        let directory = std::env::temp_dir().join(format!("fmark_restore_{}", std::process::id()));
+        let backups = Backups::new(&PathBuf::from("test.txt"), Some(directory.clone()), 2);
+        backups.create(&Bookmark::default().to_line(0, 0)).unwrap();
+
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        plain_text.set_backups(backups);
+        let mut parsed_file = parsed_file();
+        plain_text
+            .journal_mut()
+            .record(None, Some(&Bookmark::default()));
+        plain_text.journal_mut().commit();
+
+        // Test with a backup that does not exist
+        let subcommand = Subcommand::Restore { number: Some(2) };
+        assert!(subcommand.run(&mut plain_text, &mut parsed_file).is_err());
+        assert!(!plain_text.edited());
+
+        let subcommand = Subcommand::Restore { number: Some(1) };
+        assert!(subcommand.run(&mut plain_text, &mut parsed_file).is_ok());
+        assert_eq!(parsed_file.bookmarks.len(), 1);
+        assert!(parsed_file
+            .bookmarks
+            .contains_key(Bookmark::default().url()));
+        assert!(plain_text.edited());
+        // Test that edits made before the restore can no longer be undone
+        assert!(!plain_text.journal().can_undo());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_subcommand_run_undo() {
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let mut parsed_file = parsed_file();
+        assert!(Subcommand::Undo
+            .run(&mut plain_text, &mut parsed_file)
+            .is_err());
+
+        let subcommand = Subcommand::Remove {
+            url: "https://crates.io/".to_string(),
+            copy: None,
+        };
+        assert!(subcommand.run(&mut plain_text, &mut parsed_file).is_ok());
+        assert!(Subcommand::Undo
+            .run(&mut plain_text, &mut parsed_file)
+            .is_ok());
+        assert_eq!(
+            parsed_file.bookmarks["https://crates.io/"].title(),
+            "Crates"
+        );
+        assert!(Subcommand::Redo
+            .run(&mut plain_text, &mut parsed_file)
+            .is_ok());
+        assert!(!parsed_file.bookmarks.contains_key("https://crates.io/"));
+    }
+
+    #[test]
+    fn test_subcommand_dedupe() {
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let mut parsed_file = parsed_file();
+        assert_eq!(
+            Subcommand::dedupe(&parsed_file),
+            "No duplicate bookmarks found\n"
+        );
+
+        let near_duplicate = Bookmark::new(
+            "Crates.io".to_string(),
+            "Rust".to_string(),
+            "HTTPS://Crates.io?utm_source=feed".to_string(),
+        );
+        parsed_file.add_bookmark(&mut plain_text, near_duplicate);
+        let report = Subcommand::dedupe(&parsed_file);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "2 bookmarks for https://crates.io");
+        // Test that bookmarks without an added time are listed last
+        assert!(lines[1].contains("{U}{HTTPS://Crates.io?utm_source=feed}"));
+        assert!(lines[2].contains("{U}{https://crates.io/}"));
+    }
+
+    #[test]
+    fn test_subcommand_check() {
+        let subcommand = Subcommand::new(args(&["check", "-j", "2", "--redirects", "rewrite"]));
+        match subcommand {
+            Ok(Subcommand::Check {
+                jobs,
+                timeout,
+                rewrite,
+            }) => {
+                assert_eq!(jobs, 2);
+                assert_eq!(timeout, DEFAULT_CHECK_TIMEOUT);
+                assert!(rewrite);
+            }
+            _ => panic!("expected check subcommand"),
+        }
+        assert!(Subcommand::new(args(&["check", "--jobs", "0"])).is_err());
+        assert!(Subcommand::new(args(&["check", "-r", "follow"])).is_err());
+
+        let mut plain_text = PlainText::new(PathBuf::from("test.txt"));
+        let mut parsed_file = parsed_file();
+        let mut bookmarks: Vec<Bookmark> = parsed_file.all_bookmarks().cloned().collect();
+        bookmarks.sort_by(|a, b| a.url().cmp(b.url()));
+        let results = [
+            LinkStatus::Redirected {
+                location: "https://lib.rs/".to_string(),
+            },
+            LinkStatus::Broken { status: 404 },
+            LinkStatus::Redirected {
+                location: "https://news.ycombinator.com".to_string(),
+            },
+        ];
+        assert_eq!(
+            Subcommand::check_report(&bookmarks, &results),
+            "https://crates.io/: moved permanently to https://lib.rs/\n\
+             https://news.ycombinator.com/: HTTP 404\n\
+             https://www.rust-lang.org/: moved permanently to https://news.ycombinator.com\n\
+             Checked 3 bookmarks: 0 ok, 1 broken, 2 moved permanently, 0 skipped\n"
+        );
+
+        // Test that redirects to urls that are already bookmarked are not rewritten
+        let report =
+            Subcommand::rewrite_redirects(&mut plain_text, &mut parsed_file, &bookmarks, &results);
+        assert!(report.ends_with("Rewrote 1 moved URLs\n"));
+        assert!(!parsed_file.bookmarks.contains_key("https://crates.io/"));
+        assert_eq!(parsed_file.bookmarks["https://lib.rs/"].title(), "Crates");
+        assert!(parsed_file
+            .bookmarks
+            .contains_key("https://www.rust-lang.org/"));
+        assert!(plain_text.edited());
+
+        // Test that a redirect which only adds a trailing slash is rewritten
+        let bookmark = Bookmark::new(
+            "Example".to_string(),
+            "Web".to_string(),
+            "https://example.com".to_string(),
+        );
+        let mut parsed_file = ParsedFile::new(&bookmark.to_line(0, 0));
+        let results = [LinkStatus::Redirected {
+            location: "https://example.com/".to_string(),
+        }];
+        let report =
+            Subcommand::rewrite_redirects(&mut plain_text, &mut parsed_file, &[bookmark], &results);
+        assert_eq!(report, "Rewrote 1 moved URLs\n");
+        assert!(parsed_file.bookmarks.contains_key("https://example.com/"));
+        assert!(!parsed_file.bookmarks.contains_key("https://example.com"));
+    }
+}
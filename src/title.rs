@@ -0,0 +1,100 @@
+use std::process::Command;
+
+use crate::html;
+
+const FETCH_TIMEOUT: &str = "5";
+const MAX_PAGE_SIZE: &str = "1048576";
+// Keeps runaway page titles out of the bookmark file
+const MAX_TITLE_LENGTH: usize = 200;
+
+pub fn fetch(url: &str) -> Result<String, String> {
+    let output = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--location",
+            "--max-time",
+            FETCH_TIMEOUT,
+            "--max-filesize",
+            MAX_PAGE_SIZE,
+            "--",
+            url,
+        ])
+        .output()
+        .map_err(|_| "Failed to execute command: curl".to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to fetch {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    html::title(&String::from_utf8_lossy(&output.stdout))
+        .map(|title| clean(&title))
+        .filter(|title| !title.is_empty())
+        .ok_or_else(|| format!("No title found on {}", url))
+}
+
+fn clean(title: &str) -> String {
+    let title: String = title
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_TITLE_LENGTH)
+        .collect();
+    title.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_title_clean() {
+        assert_eq!(clean("Crates"), "Crates");
+        assert_eq!(clean("Bell\u{7}\u{1b}[31m Title"), "Bell[31m Title");
+        let long_title = "a".repeat(MAX_TITLE_LENGTH + 50);
+        assert_eq!(clean(&long_title).chars().count(), MAX_TITLE_LENGTH);
+        assert_eq!(
+            clean(&format!("{} b", "a".repeat(MAX_TITLE_LENGTH - 1))),
+            "a".repeat(MAX_TITLE_LENGTH - 1)
+        );
+        assert_eq!(clean("\u{1b}"), "");
+    }
+
+    #[test]
+    #[ignore = "requires curl"]
+    fn test_title_fetch() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut buffer = [0; 1024];
+                let read = stream.read(&mut buffer).unwrap_or(0);
+                let body = match String::from_utf8_lossy(&buffer[..read]).contains("GET /long ") {
+                    true => "<title>The Rust Programming Language Book, Second Edition</title>",
+                    false => "<title>Crates</title>",
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        assert_eq!(fetch(&format!("{}/", address)).unwrap(), "Crates");
+        // Test that long titles are kept whole, only the menu truncates them
+        assert_eq!(
+            fetch(&format!("{}/long", address)).unwrap(),
+            "The Rust Programming Language Book, Second Edition"
+        );
+    }
+}
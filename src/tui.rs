@@ -0,0 +1,520 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+use crate::menu::Choice;
+
+const TERMINAL_PATH: &str = "/dev/tty";
+const DEFAULT_TERMINAL_SIZE: (usize, usize) = (24, 80);
+const ESCAPE: u8 = 0x1b;
+
+const ENTER_ALTERNATE_SCREEN: &str = "\x1b[?1049h";
+const LEAVE_ALTERNATE_SCREEN: &str = "\x1b[?1049l";
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+const REVERSE_VIDEO: &str = "\x1b[7m";
+const RESET_STYLE: &str = "\x1b[0m";
+
+#[derive(Debug, PartialEq)]
+enum Key {
+    Char(char),
+    Enter,
+    AltEnter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    Up,
+    Down,
+    ClearLine,
+    DeleteWord,
+    Escape,
+    Ignored,
+}
+
+struct State<'a> {
+    items: Vec<&'a str>,
+    ignore_case: bool,
+    query: Vec<char>,
+    cursor: usize,
+    matches: Vec<&'a str>,
+    selected: usize,
+    offset: usize,
+    completed: Option<String>,
+}
+
+impl<'a> State<'a> {
+    fn new(items: Vec<&'a str>, ignore_case: bool) -> Self {
+        let mut state = Self {
+            items,
+            ignore_case,
+            query: Vec::new(),
+            cursor: 0,
+            matches: Vec::new(),
+            selected: 0,
+            offset: 0,
+            completed: None,
+        };
+        state.update_matches();
+        state
+    }
+
+    fn query(&self) -> String {
+        self.query.iter().collect()
+    }
+
+    fn update_matches(&mut self) {
+        let mut scored: Vec<(usize, usize, &'a str)> = self
+            .items
+            .iter()
+            .filter_map(|item| {
+                Self::score(item, &self.query, self.ignore_case)
+                    .map(|(gaps, first)| (gaps, first, *item))
+            })
+            .collect();
+        scored.sort_by_key(|(gaps, first, _)| (*gaps, *first));
+        self.matches = scored.into_iter().map(|(_, _, item)| item).collect();
+        self.selected = 0;
+        self.offset = 0;
+    }
+
+    // Lower scores are better: fewer characters between the matched ones, then an earlier match
+    fn score(item: &str, query: &[char], ignore_case: bool) -> Option<(usize, usize)> {
+        let Some(&first_query_char) = query.first() else {
+            return Some((0, 0));
+        };
+        let equal =
+            |a: char, b: char| a == b || (ignore_case && a.to_lowercase().eq(b.to_lowercase()));
+
+        let chars: Vec<char> = item.chars().collect();
+        let mut best = None;
+        for start in (0..chars.len()).filter(|&i| equal(chars[i], first_query_char)) {
+            let mut position = start;
+            let mut matched = 1;
+            for &query_char in &query[1..] {
+                match chars[position + 1..]
+                    .iter()
+                    .position(|&c| equal(c, query_char))
+                {
+                    Some(offset) => {
+                        position += offset + 1;
+                        matched += 1;
+                    }
+                    None => break,
+                }
+            }
+            if matched < query.len() {
+                break;
+            }
+            let score = (position + 1 - start - query.len(), start);
+            if best.is_none_or(|best| score < best) {
+                best = Some(score);
+            }
+        }
+        best
+    }
+
+    fn handle(&mut self, key: Key) -> Option<Choice> {
+        match key {
+            Key::Char(c) => {
+                self.query.insert(self.cursor, c);
+                self.cursor += 1;
+                self.update_matches();
+            }
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.query.remove(self.cursor);
+                self.update_matches();
+            }
+            Key::Delete if self.cursor < self.query.len() => {
+                self.query.remove(self.cursor);
+                self.update_matches();
+            }
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(self.query.len()),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.query.len(),
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down if self.selected + 1 < self.matches.len() => self.selected += 1,
+            Key::ClearLine => {
+                self.query.drain(..self.cursor);
+                self.cursor = 0;
+                self.update_matches();
+            }
+            Key::DeleteWord => {
+                let mut start = self.cursor;
+                while start > 0 && self.query[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                while start > 0 && !self.query[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                self.query.drain(start..self.cursor);
+                self.cursor = start;
+                self.update_matches();
+            }
+            // The highlighted item is copied into the query, so it can be edited
+            Key::Tab => {
+                if let Some(item) = self.matches.get(self.selected) {
+                    self.query = item.chars().collect();
+                    self.cursor = self.query.len();
+                    self.completed = Some(item.to_string());
+                    self.update_matches();
+                }
+            }
+            // An edited item is returned as typed text, not as the item it started from
+            Key::Enter => {
+                let edited = self
+                    .completed
+                    .as_ref()
+                    .is_some_and(|completed| completed != &self.query());
+                return Some(match self.matches.get(self.selected) {
+                    Some(item) if !self.items.is_empty() && !edited => {
+                        Choice::Selected(item.trim().to_string())
+                    }
+                    _ => Choice::Typed(self.query().trim().to_string()),
+                });
+            }
+            Key::AltEnter => return Some(Choice::Typed(self.query().trim().to_string())),
+            Key::Escape => return Some(Choice::Cancelled),
+            _ => (),
+        }
+        None
+    }
+
+    fn scroll(&mut self, rows: usize) {
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + rows {
+            self.offset = self.selected + 1 - rows;
+        }
+    }
+
+    fn render(&self, prompt: &str, rows: usize, width: usize) -> String {
+        let truncate = |line: &str| line.chars().take(width).collect::<String>();
+        let prompt = format!("{}> ", prompt);
+
+        let mut screen = String::from(CLEAR_SCREEN);
+        screen.push_str(&truncate(&format!("{}{}", prompt, self.query())));
+        for (i, item) in self.matches.iter().enumerate().skip(self.offset).take(rows) {
+            screen.push_str("\r\n");
+            match i == self.selected {
+                true => screen.push_str(&format!(
+                    "{}{}{}",
+                    REVERSE_VIDEO,
+                    truncate(item),
+                    RESET_STYLE
+                )),
+                false => screen.push_str(&truncate(item)),
+            }
+        }
+        let column = (prompt.chars().count() + self.cursor + 1).min(width);
+        screen.push_str(&format!("\x1b[1;{}H", column));
+        screen
+    }
+}
+
+struct RawMode {
+    terminal: File,
+    saved_settings: String,
+}
+
+impl RawMode {
+    fn enable(terminal: &File) -> Result<Self, String> {
+        let saved_settings = Self::stty(terminal, &["-g"])?;
+        // Reads time out after a tenth of a second, to tell Escape apart from escape sequences
+        Self::stty(terminal, &["raw", "-echo", "min", "0", "time", "1"])?;
+        let terminal = terminal
+            .try_clone()
+            .map_err(|error| format!("Failed to open the terminal: {}", error))?;
+        Ok(Self {
+            terminal,
+            saved_settings,
+        })
+    }
+
+    fn size(&self) -> (usize, usize) {
+        let size = Self::stty(&self.terminal, &["size"]).unwrap_or_default();
+        let mut size = size
+            .split_whitespace()
+            .filter_map(|n| n.parse::<usize>().ok());
+        match (size.next(), size.next()) {
+            (Some(rows), Some(columns)) if rows > 1 && columns > 0 => (rows, columns),
+            _ => DEFAULT_TERMINAL_SIZE,
+        }
+    }
+
+    fn stty(terminal: &File, args: &[&str]) -> Result<String, String> {
+        let terminal = terminal
+            .try_clone()
+            .map_err(|error| format!("Failed to open the terminal: {}", error))?;
+        let output = Command::new("stty")
+            .args(args)
+            .stdin(Stdio::from(terminal))
+            .output()
+            .map_err(|_| "Failed to execute command: stty".to_string())?;
+        match output.status.success() {
+            true => Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+            false => Err(format!(
+                "Failed to set up the terminal: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = self.terminal.write_all(LEAVE_ALTERNATE_SCREEN.as_bytes());
+        let _ = Self::stty(&self.terminal, &[&self.saved_settings]);
+    }
+}
+
+pub fn choose(
+    menu_items: Option<&str>,
+    prompt: &str,
+    rows: usize,
+    ignore_case: bool,
+) -> Result<Choice, String> {
+    let mut terminal = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(TERMINAL_PATH)
+        .map_err(|error| format!("Failed to open the terminal: {}", error))?;
+    let raw_mode = RawMode::enable(&terminal)?;
+    let (height, width) = raw_mode.size();
+    let rows = rows.clamp(1, height - 1);
+
+    let items = menu_items.unwrap_or("").lines().collect();
+    let mut state = State::new(items, ignore_case);
+    let write_error = |error: std::io::Error| format!("Failed to write to the terminal: {}", error);
+    terminal
+        .write_all(ENTER_ALTERNATE_SCREEN.as_bytes())
+        .map_err(write_error)?;
+    let choice = loop {
+        state.scroll(rows);
+        terminal
+            .write_all(state.render(prompt, rows, width).as_bytes())
+            .and_then(|_| terminal.flush())
+            .map_err(write_error)?;
+        let key = loop {
+            let key = read_key(&mut terminal)
+                .map_err(|error| format!("Failed to read from the terminal: {}", error))?;
+            if let Some(key) = key {
+                break key;
+            }
+        };
+        if let Some(choice) = state.handle(key) {
+            break choice;
+        }
+    };
+    drop(raw_mode);
+
+    Ok(choice)
+}
+
+// Returns None when no key was pressed before the read timed out
+fn read_key(reader: &mut impl Read) -> std::io::Result<Option<Key>> {
+    let Some(byte) = read_byte(reader)? else {
+        return Ok(None);
+    };
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x01 => Key::Home,
+        0x02 => Key::Left,
+        0x03 | 0x07 => Key::Escape,
+        0x04 => Key::Delete,
+        0x05 => Key::End,
+        0x06 => Key::Right,
+        0x0e => Key::Down,
+        0x10 => Key::Up,
+        0x15 => Key::ClearLine,
+        0x17 => Key::DeleteWord,
+        ESCAPE => match read_byte(reader)? {
+            None | Some(ESCAPE) => Key::Escape,
+            Some(b'\r' | b'\n') => Key::AltEnter,
+            Some(b'[' | b'O') => {
+                let mut sequence = Vec::new();
+                while let Some(byte) = read_byte(reader)? {
+                    sequence.push(byte);
+                    if (0x40..=0x7e).contains(&byte) {
+                        break;
+                    }
+                }
+                match sequence.as_slice() {
+                    b"A" => Key::Up,
+                    b"B" => Key::Down,
+                    b"C" => Key::Right,
+                    b"D" => Key::Left,
+                    b"H" | b"1~" | b"7~" => Key::Home,
+                    b"F" | b"4~" | b"8~" => Key::End,
+                    b"3~" => Key::Delete,
+                    _ => Key::Ignored,
+                }
+            }
+            Some(_) => Key::Ignored,
+        },
+        0x20..=0x7e => Key::Char(byte as char),
+        0xc0..=0xf7 => {
+            let length = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                _ => 4,
+            };
+            let mut bytes = vec![byte];
+            while bytes.len() < length {
+                match read_byte(reader)? {
+                    Some(byte) => bytes.push(byte),
+                    None => break,
+                }
+            }
+            match std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|s| s.chars().next())
+            {
+                Some(c) => Key::Char(c),
+                None => Key::Ignored,
+            }
+        }
+        _ => Key::Ignored,
+    };
+    Ok(Some(key))
+}
+
+fn read_byte(reader: &mut impl Read) -> std::io::Result<Option<u8>> {
+    let mut byte = [0];
+    match reader.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(mut input: &[u8]) -> Vec<Key> {
+        let mut keys = Vec::new();
+        while let Some(key) = read_key(&mut input).unwrap() {
+            keys.push(key);
+        }
+        keys
+    }
+
+    fn type_keys(state: &mut State, text: &str) {
+        for c in text.chars() {
+            assert!(state.handle(Key::Char(c)).is_none());
+        }
+    }
+
+    #[test]
+    fn test_tui_read_key() {
+        assert_eq!(
+            keys(b"a\x1b[A\x1b[3~\x1bOH\x1b\r\x7f\r"),
+            [
+                Key::Char('a'),
+                Key::Up,
+                Key::Delete,
+                Key::Home,
+                Key::AltEnter,
+                Key::Backspace,
+                Key::Enter
+            ]
+        );
+        assert_eq!(keys("é".as_bytes()), [Key::Char('é')]);
+
+        // Test that a lone escape is not mistaken for the start of a sequence
+        assert_eq!(keys(b"\x1b"), [Key::Escape]);
+    }
+
+    #[test]
+    fn test_tui_filter() {
+        let items = vec!["Rust Programming", "News", "Crates", "rustup"];
+        let mut state = State::new(items, true);
+        assert_eq!(state.matches.len(), 4);
+
+        // Test that contiguous matches come before spread out ones
+        type_keys(&mut state, "rs");
+        assert_eq!(state.matches, ["Rust Programming", "rustup", "Crates"]);
+
+        state.handle(Key::ClearLine);
+        type_keys(&mut state, "rust");
+        assert_eq!(state.matches, ["Rust Programming", "rustup"]);
+
+        // Test that matching respects case when asked to
+        let mut state = State::new(vec!["Rust Programming", "rustup"], false);
+        type_keys(&mut state, "rust");
+        assert_eq!(state.matches, ["rustup"]);
+    }
+
+    #[test]
+    fn test_tui_handle() {
+        let mut state = State::new(vec!["Programming", "Reading"], true);
+        state.handle(Key::Down);
+        assert_eq!(
+            state.handle(Key::Enter),
+            Some(Choice::Selected("Reading".to_string()))
+        );
+
+        // Test that a typed value is returned when nothing matches
+        type_keys(&mut state, "Rust");
+        assert!(state.matches.is_empty());
+        assert_eq!(
+            state.handle(Key::Enter),
+            Some(Choice::Typed("Rust".to_string()))
+        );
+
+        // Test inline editing of the highlighted item
+        let mut state = State::new(vec!["Old title"], true);
+        state.handle(Key::Tab);
+        state.handle(Key::DeleteWord);
+        type_keys(&mut state, "new title");
+        state.handle(Key::Home);
+        for _ in 0..4 {
+            state.handle(Key::Right);
+        }
+        state.handle(Key::Delete);
+        type_keys(&mut state, "N");
+        assert_eq!(
+            state.handle(Key::AltEnter),
+            Some(Choice::Typed("Old New title".to_string()))
+        );
+
+        // Test that Enter keeps an edit even when the edited text still matches the item
+        let mut state = State::new(vec!["Programming", "Reading"], true);
+        state.handle(Key::Tab);
+        assert_eq!(
+            state.handle(Key::Enter),
+            Some(Choice::Selected("Programming".to_string()))
+        );
+        state.handle(Key::Backspace);
+        assert_eq!(state.matches, vec!["Programming"]);
+        assert_eq!(
+            state.handle(Key::Enter),
+            Some(Choice::Typed("Programmin".to_string()))
+        );
+
+        assert_eq!(state.handle(Key::Escape), Some(Choice::Cancelled));
+    }
+
+    #[test]
+    fn test_tui_render() {
+        let items = vec!["a", "b", "c", "d"];
+        let mut state = State::new(items, true);
+        state.handle(Key::Down);
+        state.handle(Key::Down);
+        state.scroll(2);
+        let screen = state.render("bookmarks", 2, 80);
+        assert_eq!(
+            screen,
+            format!(
+                "{}bookmarks> \r\nb\r\n{}c{}\x1b[1;12H",
+                CLEAR_SCREEN, REVERSE_VIDEO, RESET_STYLE
+            )
+        );
+    }
+}